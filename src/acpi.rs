@@ -0,0 +1,122 @@
+/* minimal ACPI support: locate the RSDP and look up system description tables */
+
+use core::{mem, ptr, slice};
+use conquer_once::spin::OnceCell;
+use x86_64::PhysAddr;
+use crate::memory::phys_to_virt;
+
+// Root System Description Pointer, the ACPI 2.0 fields follow the 1.0 ones
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+// header shared by all system description tables
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+// the RSDT holds 4 byte table addresses, the XSDT 8 byte ones
+#[derive(Clone, Copy)]
+struct Root {
+    address: PhysAddr,
+    extended: bool,
+}
+
+static ROOT: OnceCell<Option<Root>> = OnceCell::uninit();
+
+// reads a (possibly unaligned) structure from physical memory
+// the caller must make sure a valid T lives at 'addr'
+pub unsafe fn read<T: Copy>(addr: PhysAddr) -> T {
+    ptr::read_unaligned(phys_to_virt(addr).as_ptr::<T>())
+}
+
+// all bytes of a valid structure sum up to zero
+unsafe fn checksum_ok(addr: PhysAddr, length: usize) -> bool {
+    let bytes = slice::from_raw_parts(phys_to_virt(addr).as_ptr::<u8>(), length);
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) == 0
+}
+
+// the RSDP lies on a 16 byte boundary in the first KiB of the EBDA
+// or in the BIOS area between 0xE0000 and 0xFFFFF
+unsafe fn find_rsdp() -> Option<PhysAddr> {
+    let ebda = u64::from(read::<u16>(PhysAddr::new(0x40e))) << 4;
+    let areas = [(ebda, ebda + 1024), (0xe0000, 0x100000)];
+
+    for (start, end) in areas {
+        if start == 0 {
+            continue;
+        }
+        for addr in (start..end).step_by(16) {
+            let addr = PhysAddr::new(addr);
+            if read::<[u8; 8]>(addr) == *b"RSD PTR " && checksum_ok(addr, 20) {
+                return Some(addr);
+            }
+        }
+    }
+    None
+}
+
+unsafe fn locate_root() -> Option<Root> {
+    let rsdp_addr = find_rsdp()?;
+    let rsdp: Rsdp = read(rsdp_addr);
+
+    // prefer the XSDT of ACPI 2.0+ firmware
+    if rsdp.revision >= 2
+        && rsdp.xsdt_address != 0
+        && checksum_ok(rsdp_addr, rsdp.length as usize)
+    {
+        Some(Root { address: PhysAddr::new(rsdp.xsdt_address), extended: true })
+    } else {
+        Some(Root { address: PhysAddr::new(u64::from(rsdp.rsdt_address)), extended: false })
+    }
+}
+
+// Returns the physical address of the first table with the given
+// signature (e.g. b"APIC" or b"HPET"), starting with its SdtHeader
+// memory::init must have been called before
+pub fn find_table(signature: &[u8; 4]) -> Option<PhysAddr> {
+    let _ = ROOT.try_init_once(|| unsafe { locate_root() });
+    let root = (*ROOT.try_get().ok()?)?;
+
+    unsafe {
+        let header: SdtHeader = read(root.address);
+        let entry_size = if root.extended { 8 } else { 4 };
+        let entries = root.address + mem::size_of::<SdtHeader>();
+        let count = (header.length as usize - mem::size_of::<SdtHeader>()) / entry_size;
+
+        for i in 0..count {
+            let entry = entries + i * entry_size;
+            let table = if root.extended {
+                read::<u64>(entry)
+            } else {
+                u64::from(read::<u32>(entry))
+            };
+            let table = PhysAddr::new(table);
+            let header: SdtHeader = read(table);
+            if header.signature == *signature && checksum_ok(table, header.length as usize) {
+                return Some(table);
+            }
+        }
+    }
+    None
+}
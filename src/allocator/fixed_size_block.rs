@@ -58,7 +58,7 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
         // get mutable reference
         let mut allocator = self.lock();
         // calculate the appropriate block size corresponding index
-        match list_index(&layout) {
+        let ptr = match list_index(&layout) {
             Some(index) => {
                 match allocator.list_heads[index].take() {
                     Some(node) => {
@@ -80,11 +80,16 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
             // no block size fits for the allocation
             // use fallback allocator
             None => allocator.fallback_alloc(layout),
+        };
+        if !ptr.is_null() {
+            super::record_alloc(layout.size());
         }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let mut allocator = self.lock();
+        super::record_dealloc(layout.size());
 
         match list_index(&layout) {
             Some(index) => {
@@ -4,8 +4,12 @@ mod bump;
 mod linked_list;
 mod fixed_size_block;
 
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
 use core::ptr:: null_mut;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use alloc::alloc::{GlobalAlloc, Layout};
+use x86_64::instructions::interrupts;
 use x86_64::{
     structures::paging::{
         mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB
@@ -18,7 +22,7 @@ use x86_64::{
 use self::fixed_size_block::FixedSizeBlockAllocator;
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
-pub const HEAP_SIZE: usize = 100 * 1024; // 100KB
+pub const HEAP_SIZE: usize = 1024 * 1024; // 1MB, room for thread stacks
 
 #[global_allocator] // use the fllowing function as the global allocator
 // Locked type: spinlock type for synchronization
@@ -26,6 +30,35 @@ pub const HEAP_SIZE: usize = 100 * 1024; // 100KB
 // static ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
 static ALLOCATOR: Locked<FixedSizeBlockAllocator> = Locked::new(FixedSizeBlockAllocator::new());
 
+// bytes requested by live allocations and how many there are
+static USED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub size: usize,
+    pub used: usize, // as requested, block rounding and fragmentation not included
+    pub allocations: usize,
+}
+
+pub fn stats() -> HeapStats {
+    HeapStats {
+        size: HEAP_SIZE,
+        used: USED.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
+
+fn record_alloc(size: usize) {
+    USED.fetch_add(size, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    USED.fetch_sub(size, Ordering::Relaxed);
+    ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+}
+
 
 // map heap region to physical memory
 // This function take mutable references to a Mapper and a FrameAllocator instance
@@ -80,9 +113,45 @@ impl <A> Locked<A> {
             inner: spin::Mutex::new(inner),
         }
     }
-    // lock on the wrapped Mutex
-    pub fn lock(&self) -> spin::MutexGuard<A> {
-        self.inner.lock()
+    // lock on the wrapped Mutex with interrupts disabled until the guard is
+    // dropped: code holding the allocator lock must neither be preempted by
+    // the thread scheduler nor interrupted by a handler that allocates
+    pub fn lock(&self) -> LockedGuard<A> {
+        let enable = interrupts::are_enabled();
+        interrupts::disable();
+        LockedGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            enable,
+        }
+    }
+}
+
+pub struct LockedGuard<'a, A> {
+    guard: ManuallyDrop<spin::MutexGuard<'a, A>>,
+    enable: bool, // whether interrupts were enabled before locking
+}
+
+impl<A> Deref for LockedGuard<'_, A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.guard
+    }
+}
+
+impl<A> DerefMut for LockedGuard<'_, A> {
+    fn deref_mut(&mut self) -> &mut A {
+        &mut self.guard
+    }
+}
+
+impl<A> Drop for LockedGuard<'_, A> {
+    fn drop(&mut self) {
+        // unlock before an interrupt can come in
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if self.enable {
+            interrupts::enable();
+        }
     }
 }
 
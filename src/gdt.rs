@@ -1,5 +1,6 @@
 /* impelmentation of Global Descriptor Table */
 
+use alloc::{boxed::Box, vec};
 use core::ptr::addr_of;
 use x86_64::VirtAddr;
 use x86_64::structures::tss::TaskStateSegment;
@@ -8,20 +9,28 @@ use x86_64::structures::gdt::{GlobalDescriptorTable, Descriptor};
 use lazy_static::lazy_static;
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const NMI_IST_INDEX: u16 = 1;
 
-// Task State Segment
+const IST_STACK_SIZE: usize = 4096 * 5;
+
+// a TSS whose interrupt stack table points at the given stack ends
+fn new_tss(double_fault_stack_end: VirtAddr, nmi_stack_end: VirtAddr) -> TaskStateSegment {
+    let mut tss = TaskStateSegment::new();
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = double_fault_stack_end;
+    // an NMI can arrive at any instruction, even while the kernel stack is unusable
+    tss.interrupt_stack_table[NMI_IST_INDEX as usize] = nmi_stack_end;
+    tss
+}
+
+// Task State Segment of the bootstrap processor
 lazy_static! {
     static ref TSS: TaskStateSegment = {
-        let mut tss = TaskStateSegment::new();
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-            const STACK_SIZE: usize = 4096 * 5;
-            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
-
-            let stack_start = VirtAddr::from_ptr(unsafe { addr_of!(STACK) });
-            let stack_end = stack_start + STACK_SIZE;
-            stack_end
-        };
-        tss
+        static mut DOUBLE_FAULT_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+        static mut NMI_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+
+        let double_fault_stack = VirtAddr::from_ptr(unsafe { addr_of!(DOUBLE_FAULT_STACK) });
+        let nmi_stack = VirtAddr::from_ptr(unsafe { addr_of!(NMI_STACK) });
+        new_tss(double_fault_stack + IST_STACK_SIZE, nmi_stack + IST_STACK_SIZE)
     };
 }
 
@@ -31,24 +40,44 @@ struct Selectors {
     tss_selector: SegmentSelector,
 }
 
-// Global Descriptor Table
+fn new_gdt(tss: &'static TaskStateSegment) -> (GlobalDescriptorTable, Selectors) {
+    let mut gdt = GlobalDescriptorTable::new();
+    let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
+    let tss_selector = gdt.add_entry(Descriptor::tss_segment(tss));
+    (gdt, Selectors { code_selector, tss_selector })
+}
+
+// Global Descriptor Table of the bootstrap processor
 lazy_static! {
-    static ref GDT: (GlobalDescriptorTable, Selectors) = {
-        let mut gdt = GlobalDescriptorTable::new();
-        let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
-        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
-        (gdt, Selectors { code_selector, tss_selector })
-    };
+    static ref GDT: (GlobalDescriptorTable, Selectors) = new_gdt(&TSS);
 }
 
-pub fn init() {
+fn load(gdt: &'static (GlobalDescriptorTable, Selectors)) {
     use x86_64::instructions::tables::load_tss;
     use x86_64::instructions::segmentation::{CS, Segment};
 
-    GDT.0.load();
+    gdt.0.load();
     unsafe {
         // overwrite .text segement register and overload TSS
-        CS::set_reg(GDT.1.code_selector);
-        load_tss(GDT.1.tss_selector);
+        CS::set_reg(gdt.1.code_selector);
+        load_tss(gdt.1.tss_selector);
     }
-}
\ No newline at end of file
+}
+
+pub fn init() {
+    load(&GDT);
+}
+
+// Called by every application processor as it comes up
+// a TSS is marked busy once loaded, so no two CPUs can share one, and
+// each needs its own IST stacks anyway; the heap must be initialized
+pub(crate) fn init_ap() {
+    // never freed, the CPU uses them until it is turned off
+    let stack_end = || {
+        let stack = Box::leak(vec![0u8; IST_STACK_SIZE].into_boxed_slice());
+        VirtAddr::from_ptr(stack.as_ptr()) + IST_STACK_SIZE
+    };
+    let tss = Box::leak(Box::new(new_tss(stack_end(), stack_end())));
+    let gdt = Box::leak(Box::new(new_gdt(tss)));
+    load(gdt);
+}
@@ -1,10 +1,10 @@
 /* implemention of interrupt descriptor table */
 
 mod pic8259;
+pub mod stats;
 
 use spin;
 use crate::gdt;
-use crate::print;
 use crate::println;
 use crate::hlt_loop;
 use pic8259::ChainedPics;
@@ -23,6 +23,13 @@ pub static PICS: spin::Mutex<ChainedPics> =
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    Serial = PIC_1_OFFSET + 4,         // IRQ 4, COM1
+    SpuriousMaster = PIC_1_OFFSET + 7, // IRQ 7
+    Rtc = PIC_2_OFFSET,                // IRQ 8
+    SpuriousSlave = PIC_2_OFFSET + 7,  // IRQ 15
+    Wakeup = crate::smp::apic::WAKEUP_VECTOR,
+    Watchdog = crate::smp::apic::WATCHDOG_VECTOR,
+    ApicSpurious = crate::smp::apic::SPURIOUS_VECTOR,
 }
 
 impl InterruptIndex {
@@ -47,11 +54,27 @@ lazy_static! {
             idt.double_fault.set_handler_fn(double_fault_handler)
             // In some situation such as stack overflow, need switch the stack to run normally
             .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX); // when call stack by name maybe induce fault
+            idt.non_maskable_interrupt.set_handler_fn(nmi_handler)
+            .set_stack_index(gdt::NMI_IST_INDEX);
         }
         idt[InterruptIndex::Timer.as_usize()]
             .set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard.as_usize()]
             .set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Serial.as_usize()]
+            .set_handler_fn(serial_interrupt_handler);
+        idt[InterruptIndex::Rtc.as_usize()]
+            .set_handler_fn(rtc_interrupt_handler);
+        idt[InterruptIndex::SpuriousMaster.as_usize()]
+            .set_handler_fn(spurious_master_interrupt_handler);
+        idt[InterruptIndex::SpuriousSlave.as_usize()]
+            .set_handler_fn(spurious_slave_interrupt_handler);
+        idt[InterruptIndex::Wakeup.as_usize()]
+            .set_handler_fn(wakeup_interrupt_handler);
+        idt[InterruptIndex::Watchdog.as_usize()]
+            .set_handler_fn(watchdog_interrupt_handler);
+        idt[InterruptIndex::ApicSpurious.as_usize()]
+            .set_handler_fn(apic_spurious_interrupt_handler);
         idt
     };
 }
@@ -61,12 +84,21 @@ pub fn init_idt() {
     IDT.load();
 }
 
+// number of spurious interrupts seen on the master and the slave PIC
+pub fn spurious_interrupts() -> [u64; 2] {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        PICS.lock().spurious_counts()
+    })
+}
+
 // CPU will start receiving timer interrupts when enable interrupt
 // so we need a timer interrupt
 extern "x86-interrupt" fn timer_interrupt_handler(
-    _stack_frame: InterruptStackFrame)
+    stack_frame: InterruptStackFrame)
 {
-    print!(".");
+    let measure = stats::Measure::start(InterruptIndex::Timer.as_u8());
+    crate::time::tick();
+    crate::watchdog::check(&stack_frame);
 
     // PIC except an explicit "end of interrupt" signal after the interrupt was processed
     // so we need a reply
@@ -74,6 +106,10 @@ extern "x86-interrupt" fn timer_interrupt_handler(
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
     }
+
+    // another thread may run for a while before this handler returns
+    drop(measure);
+    crate::thread::preempt();
 }
 
 // when press a key, the keyboard controller will send a interrupt
@@ -83,6 +119,7 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(
 {
     use x86_64::instructions::port::Port;
 
+    let _measure = stats::Measure::start(InterruptIndex::Keyboard.as_u8());
     let mut port = Port::new(0x60); // PS/2 controller
     let scancode: u8 = unsafe { port.read() };
     crate::task::keyboard::add_scancode(scancode);
@@ -94,6 +131,80 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(
 
 }
 
+// COM1 received data once serial::enable_receive_interrupt was called
+extern "x86-interrupt" fn serial_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::Serial.as_u8());
+    while let Some(byte) = crate::serial::try_receive() {
+        crate::task::keyboard::add_serial_byte(byte);
+    }
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Serial.as_u8());
+    }
+}
+
+// the CMOS clock raises IRQ 8 periodically once rtc::enable_periodic_interrupt was called
+extern "x86-interrupt" fn rtc_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::Rtc.as_u8());
+    crate::rtc::handle_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Rtc.as_u8());
+    }
+}
+
+// the PICs raise their lowest priority line when a request disappears
+// before the CPU acknowledges it, ChainedPics sorts out which EOI is due
+extern "x86-interrupt" fn spurious_master_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::SpuriousMaster.as_u8());
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::SpuriousMaster.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn spurious_slave_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::SpuriousSlave.as_u8());
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::SpuriousSlave.as_u8());
+    }
+}
+
+// another CPU queued work for this one, waking it from hlt is all it takes
+extern "x86-interrupt" fn wakeup_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::Wakeup.as_u8());
+    crate::smp::apic::end_of_interrupt();
+}
+
+// the local timer of an AP, so a stuck BSP is noticed as well
+extern "x86-interrupt" fn watchdog_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::Watchdog.as_u8());
+    crate::watchdog::check_other_cpus();
+    crate::smp::apic::end_of_interrupt();
+}
+
+// the local APIC expects no end of interrupt for its spurious vector
+extern "x86-interrupt" fn apic_spurious_interrupt_handler(
+    _stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(InterruptIndex::ApicSpurious.as_u8());
+}
+
 // set page fault exception
 extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
@@ -101,6 +212,7 @@ extern "x86-interrupt" fn page_fault_handler(
 ) {
     use x86_64::registers::control::Cr2;
 
+    stats::record(14);
     println!("EXCEPTION: PAGE FAULT");
     println!("Accessed Address: {:?}", Cr2::read()); // CR2 register store the virtual address that caused the page fault
     println!("Error Code: {:?}", error_code);
@@ -112,12 +224,30 @@ extern "x86-interrupt" fn page_fault_handler(
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame, _error_code: u64) -> !
 {
+    stats::record(8);
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
+// non-maskable interrupts arrive even while the kernel spins with interrupts
+// disabled, so use them to find out where it is stuck
+extern "x86-interrupt" fn nmi_handler(
+    stack_frame: InterruptStackFrame)
+{
+    let _measure = stats::Measure::start(2);
+    crate::watchdog::nmi(&stack_frame);
+}
+
 // set breakpoint exception
 extern "x86-interrupt" fn breakpoint_handler(
     stack_frame: InterruptStackFrame)
 {
+    let _measure = stats::Measure::start(3);
     println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
 }
+
+#[test_case]
+fn test_breakpoint_counted() {
+    let before = stats::count(3);
+    x86_64::instructions::interrupts::int3(); // invoke a breakpoint exception
+    assert_eq!(stats::count(3), before + 1);
+}
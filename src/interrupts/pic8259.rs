@@ -10,6 +10,16 @@ const CMD_INIT: u8 = 0x11;
 // command sent to acknowledge an interrupt
 const CMD_END_OF_INTERRUPT: u8 = 0x20;
 
+// OCW3 commands selecting which register the next command port read returns
+const CMD_READ_IRR: u8 = 0x0a; // interrupt request register: raised but not yet serviced
+const CMD_READ_ISR: u8 = 0x0b; // in-service register: currently being serviced
+
+// the master line the slave PIC is wired to
+const CASCADE_IRQ: u8 = 2;
+
+// the lowest priority line of each PIC, used when an IRQ vanishes before being acknowledged
+const SPURIOUS_IRQ: u8 = 7;
+
 // the mode in which we want to run ours PICs
 const MODE_8086: u8 = 0x01;
 
@@ -31,6 +41,25 @@ impl Pic {
         self.command.write(CMD_END_OF_INTERRUPT);
     }
 
+    // reads the in-service register of this PIC
+    unsafe fn read_isr(&mut self) -> u8 {
+        self.command.write(CMD_READ_ISR);
+        self.command.read()
+    }
+
+    // reads the interrupt request register of this PIC
+    unsafe fn read_irr(&mut self) -> u8 {
+        self.command.write(CMD_READ_IRR);
+        self.command.read()
+    }
+
+    // an interrupt on the lowest priority line is spurious if the PIC
+    // did not actually mark it as in service
+    unsafe fn is_spurious(&mut self, interrupt_id: u8) -> bool {
+        interrupt_id == self.offset + SPURIOUS_IRQ
+            && self.read_isr() & (1 << SPURIOUS_IRQ) == 0
+    }
+
     // reads the interrupt mask of this PIC
     unsafe fn read_mask(&mut self) -> u8 {
         self.data.read()
@@ -45,6 +74,7 @@ impl Pic {
 // a pair of chained PIC controllers
 pub struct ChainedPics {
     pics: [Pic; 2],
+    spurious: [u64; 2], // spurious interrupts seen on the master and the slave
 }
 
 impl ChainedPics {
@@ -62,6 +92,7 @@ impl ChainedPics {
                     data: Port::new(0xA1),
                 },
             ],
+            spurious: [0; 2],
         }
     }
 
@@ -102,6 +133,29 @@ impl ChainedPics {
         self.pics[1].write_mask(mask2);
     }
 
+    // enables a single IRQ line (0-15), unmasking the cascade for slave lines
+    pub unsafe fn unmask(&mut self, irq: u8) {
+        let [mut master, mut slave] = self.read_masks();
+        if irq < 8 {
+            master &= !(1 << irq);
+        } else {
+            slave &= !(1 << (irq - 8));
+            master &= !(1 << CASCADE_IRQ);
+        }
+        self.write_masks(master, slave);
+    }
+
+    // disables a single IRQ line (0-15)
+    pub unsafe fn mask(&mut self, irq: u8) {
+        let [mut master, mut slave] = self.read_masks();
+        if irq < 8 {
+            master |= 1 << irq;
+        } else {
+            slave |= 1 << (irq - 8);
+        }
+        self.write_masks(master, slave);
+    }
+
     pub unsafe fn disable(&mut self) {
         self.write_masks(u8::MAX, u8::MAX);
     }
@@ -110,14 +164,42 @@ impl ChainedPics {
         self.pics.iter().any(|p| p.handles_interrupt(interrupt_id))
     }
 
+    // in-service registers of both PICs, the slave in the high byte
+    pub unsafe fn read_isr(&mut self) -> u16 {
+        u16::from(self.pics[1].read_isr()) << 8 | u16::from(self.pics[0].read_isr())
+    }
+
+    // interrupt request registers of both PICs, the slave in the high byte
+    pub unsafe fn read_irr(&mut self) -> u16 {
+        u16::from(self.pics[1].read_irr()) << 8 | u16::from(self.pics[0].read_irr())
+    }
+
+    // number of spurious interrupts seen on the master and the slave
+    pub fn spurious_counts(&self) -> [u64; 2] {
+        self.spurious
+    }
+
     // figure out which PICs in our chain need to know about this interrupt
-    pub unsafe fn notify_end_of_interrupt(&mut self, interrupt_id: u8) {
-        if self.handles_interrupt(interrupt_id) {
-            if self.pics[1].handles_interrupt(interrupt_id) {
-                self.pics[1].end_of_interrupt();
+    // returns false if the interrupt was spurious and must not be handled
+    pub unsafe fn notify_end_of_interrupt(&mut self, interrupt_id: u8) -> bool {
+        if !self.handles_interrupt(interrupt_id) {
+            return true;
+        }
+        if self.pics[1].handles_interrupt(interrupt_id) {
+            if self.pics[1].is_spurious(interrupt_id) {
+                // the master still saw a real request on the cascade line
+                self.spurious[1] += 1;
+                self.pics[0].end_of_interrupt();
+                return false;
             }
-            self.pics[0].end_of_interrupt();
+            self.pics[1].end_of_interrupt();
+        } else if self.pics[0].is_spurious(interrupt_id) {
+            // nothing is in service, so no PIC expects an EOI
+            self.spurious[0] += 1;
+            return false;
         }
+        self.pics[0].end_of_interrupt();
+        true
     }
 
 }
\ No newline at end of file
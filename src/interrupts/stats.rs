@@ -0,0 +1,100 @@
+/* per-vector interrupt statistics, printable like /proc/interrupts */
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::InterruptIndex;
+use crate::time::tsc;
+
+const VECTORS: usize = 256;
+
+// an array of atomics can't be built with [AtomicU64::new(0); N] directly
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+static COUNTS: [AtomicU64; VECTORS] = [ZERO; VECTORS];
+static CYCLES: [AtomicU64; VECTORS] = [ZERO; VECTORS];
+
+// created at the start of a handler, adds the elapsed cycles when dropped
+pub(crate) struct Measure {
+    vector: u8,
+    start: u64,
+}
+
+impl Measure {
+    pub(crate) fn start(vector: u8) -> Measure {
+        COUNTS[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+        Measure { vector, start: tsc::read() }
+    }
+}
+
+impl Drop for Measure {
+    fn drop(&mut self) {
+        let elapsed = tsc::read().wrapping_sub(self.start);
+        CYCLES[usize::from(self.vector)].fetch_add(elapsed, Ordering::Relaxed);
+    }
+}
+
+// count an interrupt whose handler never returns
+pub(crate) fn record(vector: u8) {
+    COUNTS[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+}
+
+// how many times the handler of 'vector' ran
+pub fn count(vector: u8) -> u64 {
+    COUNTS[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+// total TSC cycles spent in the handler of 'vector'
+pub fn cycles(vector: u8) -> u64 {
+    CYCLES[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+// human readable name of a vector
+pub fn name(vector: u8) -> &'static str {
+    const EXCEPTIONS: [&str; 32] = [
+        "divide error", "debug", "non-maskable interrupt", "breakpoint",
+        "overflow", "bound range exceeded", "invalid opcode", "device not available",
+        "double fault", "coprocessor segment overrun", "invalid TSS", "segment not present",
+        "stack-segment fault", "general protection fault", "page fault", "reserved",
+        "x87 floating-point", "alignment check", "machine check", "SIMD floating-point",
+        "virtualization", "control protection", "reserved", "reserved",
+        "reserved", "reserved", "reserved", "reserved",
+        "hypervisor injection", "VMM communication", "security", "reserved",
+    ];
+
+    match vector {
+        v if v < 32 => EXCEPTIONS[usize::from(v)],
+        v if v == InterruptIndex::Timer.as_u8() => "timer",
+        v if v == InterruptIndex::Keyboard.as_u8() => "keyboard",
+        v if v == InterruptIndex::Serial.as_u8() => "serial (COM1)",
+        v if v == InterruptIndex::Rtc.as_u8() => "real-time clock",
+        v if v == InterruptIndex::SpuriousMaster.as_u8() => "spurious (master PIC)",
+        v if v == InterruptIndex::SpuriousSlave.as_u8() => "spurious (slave PIC)",
+        v if v == InterruptIndex::Wakeup.as_u8() => "wakeup IPI",
+        v if v == InterruptIndex::Watchdog.as_u8() => "watchdog (local APIC timer)",
+        v if v == InterruptIndex::ApicSpurious.as_u8() => "spurious (local APIC)",
+        _ => "unknown",
+    }
+}
+
+// a snapshot-free view over the counters, printed one line per vector that fired
+pub struct Table;
+
+pub fn table() -> Table {
+    Table
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>4} {:>12} {:>14} {:>10}  {}", "VEC", "COUNT", "CYCLES", "AVG", "NAME")?;
+        for vector in 0..=u8::MAX {
+            let count = count(vector);
+            if count == 0 {
+                continue;
+            }
+            let cycles = cycles(vector);
+            writeln!(f, "{:>4} {:>12} {:>14} {:>10}  {}",
+                vector, count, cycles, cycles / count, name(vector))?;
+        }
+        Ok(())
+    }
+}
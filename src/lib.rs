@@ -11,12 +11,19 @@
 extern crate alloc;
 
 pub mod gdt;
+pub mod rtc;
+pub mod acpi;
+pub mod smp;
+pub mod time;
+pub mod watchdog;
+pub mod thread;
 pub mod task;
 pub mod serial;
 pub mod memory;
 pub mod allocator;
 pub mod vga_buffer;
 pub mod interrupts;
+pub mod shell;
 use core::panic::PanicInfo;
 
 
@@ -56,6 +63,7 @@ where
 // halt CPU before next interrupt occur for decreasing the CPU usage
 pub fn hlt_loop() -> ! {
     loop {
+        watchdog::pet();
         x86_64::instructions::hlt();
     }
 }
@@ -80,6 +88,7 @@ pub fn init() {
     gdt::init();
     interrupts::init_idt();
     unsafe { interrupts::PICS.lock().initialize() };
+    time::pit::set_frequency(time::TICK_HZ);
     // enable interrupt
     x86_64::instructions::interrupts::enable();
 
@@ -93,10 +102,19 @@ entry_point!(test_kernel_main);
 
 // Entry point for "cargo test"
 #[cfg(test)] // only for test
-fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    use memory::BootInfoFrameAllocator;
+    use x86_64::VirtAddr;
+
     // lib.rs is independtly of the main.rs
     // so add it when running test lib
     init();
+    // unit tests that allocate, like the line editor's, need the heap
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("heap initialization failed");
     test_main();
     hlt_loop();
 
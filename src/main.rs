@@ -11,9 +11,10 @@
 
 extern crate alloc;
 
-use blog_os::print;
 use blog_os::println;
-use blog_os::task::{Task, simple_executor::SimpleExecutor};
+use blog_os::shell;
+use blog_os::vga_buffer;
+use blog_os::task::{keyboard, work_stealing};
 use bootloader::{BootInfo, entry_point};
 use core::panic::PanicInfo;
 
@@ -30,30 +31,64 @@ async fn example_task() {
     println!("async number: {}", number);
 }
 
-fn kernel_main(_boot_info: &'static BootInfo) -> ! {
+// layout at boot, keyboard::set_layout switches it later
+const KEYBOARD_LAYOUT: keyboard::Layout = keyboard::Layout::Us;
+
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    use blog_os::allocator;
+    use blog_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
 
     println!("Hell♂ W♀rld{}", "!");
     blog_os::init();
 
-    // asynchronous example
-    let mut executor = SimpleExecutor::new();
-    executor.spawn(Task::new(example_task()));
-    executor.run();
+    // paging and heap
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe {
+        BootInfoFrameAllocator::init(&boot_info.memory_map)
+    };
+    allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("heap initialization failed");
+    blog_os::vga_buffer::init_scrollback(blog_os::vga_buffer::DEFAULT_SCROLLBACK_LEN);
+    blog_os::thread::init();
+
+    // clocks need the physical memory mapping to find the HPET
+    blog_os::time::init();
+    println!("{} (clock source: {:?})", blog_os::rtc::now(), blog_os::time::source());
+    blog_os::smp::init(&mut mapper, &mut frame_allocator);
+    println!("{} CPUs online", blog_os::smp::cpu_count());
+    blog_os::watchdog::enable(5000);
 
     #[cfg(test)]
     test_main();
 
     println!("It did not crash!");
-    blog_os::hlt_loop();
 
+    // asynchronous example, every CPU runs tasks and halts while none is ready
+    work_stealing::spawn(example_task());
+    keyboard::set_layout(KEYBOARD_LAYOUT);
+    work_stealing::spawn(keyboard::run(keyboard::ScancodeSet::Set1));
+    // a terminal on COM1 types into the same key events as the keyboard,
+    // which go to the console on the screen
+    work_stealing::spawn(keyboard::run_serial());
+    // a shell on every virtual console, Alt+F1..F6 switch between them
+    for console in 0..vga_buffer::console::CONSOLE_COUNT {
+        work_stealing::spawn(shell::run(console));
+    }
+    work_stealing::spawn(vga_buffer::console::run());
+    work_stealing::run();
 }
 
 // This function is called on panic
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
-    blog_os::hlt_loop();
+    // println! goes to console 0, which may be hidden behind another one
+    let console = vga_buffer::console::switch_for_panic();
+    vga_buffer::console::print_to(console, format_args!("{}\n", info));
+    // long messages scroll off the screen, keep them reachable
+    keyboard::scroll_after_panic();
 }
 
 // panic for test
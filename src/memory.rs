@@ -1,11 +1,32 @@
 /* paging implementation: Map the complete physical memory somewhere into the virtual address space */
 
+use core::sync::atomic::{AtomicU64, Ordering};
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
 use x86_64::{
-    structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB}, PhysAddr, VirtAddr
+    structures::paging::{FrameAllocator, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame, Size4KiB}, PhysAddr, VirtAddr
     
 };
 
+// where the bootloader mapped the complete physical memory, set by 'init'
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+// usable frames in the memory map and how many BootInfoFrameAllocator handed out
+static FRAMES_TOTAL: AtomicU64 = AtomicU64::new(0);
+static FRAMES_USED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub total: u64,
+    pub used: u64,
+}
+
+pub fn frame_stats() -> FrameStats {
+    FrameStats {
+        total: FRAMES_TOTAL.load(Ordering::Relaxed),
+        used: FRAMES_USED.load(Ordering::Relaxed),
+    }
+}
+
 // a frame allocator that returns usable frames from the bootloader's memory map
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryMap,
@@ -15,10 +36,12 @@ pub struct BootInfoFrameAllocator {
 impl BootInfoFrameAllocator {
     // we must check the passed memory map is valid
     pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
-        BootInfoFrameAllocator {
+        let allocator = BootInfoFrameAllocator {
             memory_map,
             next: 0,
-        }
+        };
+        FRAMES_TOTAL.store(allocator.usable_frames().count() as u64, Ordering::Relaxed);
+        allocator
     }
 
     // returns an iterator over the usable frames specified in the memory map
@@ -41,6 +64,9 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
         let frame = self.usable_frames().nth(self.next);
         self.next += 1;
+        if frame.is_some() {
+            FRAMES_USED.fetch_add(1, Ordering::Relaxed);
+        }
         frame
     }
 }
@@ -52,10 +78,43 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
 // 'physcial_memory_offset'. Also, this function must be only called once
 // to avoid aliasing '&mut' references (which is undefined behavior).
 pub unsafe fn init(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
     let level_4_table = active_level_4_table(physical_memory_offset);
     OffsetPageTable::new(level_4_table, physical_memory_offset)
 }
 
+// Returns the virtual address through which the given physical address
+// (RAM, ACPI tables or memory-mapped registers) can be accessed
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr {
+    let offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed);
+    assert!(offset != 0, "memory::init must be called first");
+    VirtAddr::new(offset + addr.as_u64())
+}
+
+// Map or unmap a page that is mapped with 4 KiB pages, keeping its frame,
+// e.g. to turn a heap page into a guard page and back
+// This function is unsafe because the caller must own the page: accesses to
+// it fault while it is not present. Other CPUs may still reach it through
+// their TLB until they flush it.
+pub unsafe fn set_present(page: Page<Size4KiB>, present: bool) {
+    use x86_64::registers::control::Cr3;
+
+    let addr = page.start_address();
+    let (level_4_table_frame, _) = Cr3::read();
+    let mut table: &mut PageTable = &mut *phys_to_virt(level_4_table_frame.start_address()).as_mut_ptr();
+    for &index in &[addr.p4_index(), addr.p3_index(), addr.p2_index()] {
+        let entry = &table[index];
+        assert!(!entry.flags().contains(PageTableFlags::HUGE_PAGE), "page {:?} is part of a huge page", page);
+        let frame = entry.frame().expect("page not mapped");
+        table = &mut *phys_to_virt(frame.start_address()).as_mut_ptr();
+    }
+    let entry = &mut table[addr.p1_index()];
+    let mut flags = entry.flags();
+    flags.set(PageTableFlags::PRESENT, present);
+    entry.set_flags(flags);
+    x86_64::instructions::tlb::flush(addr);
+}
+
 // Private
 unsafe fn active_level_4_table(physical_memory_offset: VirtAddr)
     -> &'static mut PageTable
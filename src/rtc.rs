@@ -0,0 +1,215 @@
+/* CMOS real-time clock: wall-clock date and time through ports 0x70/0x71 */
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicU32, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+// CMOS registers
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+const REG_STATUS_C: u8 = 0x0c;
+const REG_CENTURY: u8 = 0x32; // not standardized, but present on QEMU and most PCs
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 0x40;
+const STATUS_B_BINARY: u8 = 0x04; // values are binary instead of BCD
+const STATUS_B_24_HOUR: u8 = 0x02;
+const HOUR_PM: u8 = 0x80; // set in the hour register for PM in 12-hour mode
+
+// the RTC sits on IRQ 8, the first line of the slave PIC
+const RTC_IRQ: u8 = 8;
+
+// the index/data port pair of the CMOS
+struct Cmos {
+    address: Port<u8>,
+    data: Port<u8>,
+}
+
+impl Cmos {
+    const fn new() -> Cmos {
+        Cmos {
+            address: Port::new(0x70),
+            data: Port::new(0x71),
+        }
+    }
+
+    unsafe fn read(&mut self, register: u8) -> u8 {
+        self.address.write(register);
+        self.data.read()
+    }
+
+    unsafe fn write(&mut self, register: u8, value: u8) {
+        self.address.write(register);
+        self.data.write(value);
+    }
+
+    // the clock must not be read while it is updating its registers
+    unsafe fn update_in_progress(&mut self) -> bool {
+        self.read(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0
+    }
+
+    unsafe fn read_registers(&mut self) -> [u8; 7] {
+        while self.update_in_progress() {}
+        [
+            self.read(REG_SECONDS),
+            self.read(REG_MINUTES),
+            self.read(REG_HOURS),
+            self.read(REG_DAY),
+            self.read(REG_MONTH),
+            self.read(REG_YEAR),
+            self.read(REG_CENTURY),
+        ]
+    }
+}
+
+static CMOS: Mutex<Cmos> = Mutex::new(Cmos::new());
+
+// periodic interrupts since enable_periodic_interrupt and their frequency
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0f) + (value >> 4) * 10
+}
+
+// convert raw register values according to the format in status register B
+fn decode(raw: [u8; 7], status_b: u8) -> DateTime {
+    let [second, minute, hour, day, month, year, century] = raw;
+    let binary = |value: u8| {
+        if status_b & STATUS_B_BINARY != 0 { value } else { bcd_to_binary(value) }
+    };
+
+    // the PM flag is not part of the BCD value
+    let pm = hour & HOUR_PM != 0;
+    let mut hour = binary(hour & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        // 12 AM is midnight and 12 PM is noon
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+
+    let century = match binary(century) {
+        c @ 19..=21 => u16::from(c),
+        _ => 20, // no usable century register
+    };
+
+    DateTime {
+        year: century * 100 + u16::from(binary(year)),
+        month: binary(month),
+        day: binary(day),
+        hour,
+        minute: binary(minute),
+        second: binary(second),
+    }
+}
+
+// read the current wall-clock time
+pub fn now() -> DateTime {
+    interrupts::without_interrupts(|| {
+        let mut cmos = CMOS.lock();
+        unsafe {
+            // read until two consecutive reads agree so an update in
+            // between can't give us a torn value
+            let mut raw = cmos.read_registers();
+            loop {
+                let again = cmos.read_registers();
+                if again == raw {
+                    break;
+                }
+                raw = again;
+            }
+            decode(raw, cmos.read(REG_STATUS_B))
+        }
+    })
+}
+
+// enable the periodic interrupt on IRQ 8 at 32768 >> (rate - 1) Hz
+// rate must be in 3..=15, which gives 8 kHz down to 2 Hz
+pub fn enable_periodic_interrupt(rate: u8) {
+    assert!((3..=15).contains(&rate), "RTC rate must be between 3 and 15");
+
+    interrupts::without_interrupts(|| {
+        let mut cmos = CMOS.lock();
+        unsafe {
+            let status_a = cmos.read(REG_STATUS_A);
+            cmos.write(REG_STATUS_A, (status_a & 0xf0) | rate);
+            let status_b = cmos.read(REG_STATUS_B);
+            cmos.write(REG_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+            // clear a pending interrupt, otherwise IRQ 8 never fires
+            cmos.read(REG_STATUS_C);
+        }
+        FREQUENCY.store(32768 >> (rate - 1), Ordering::Relaxed);
+        unsafe { crate::interrupts::PICS.lock().unmask(RTC_IRQ) };
+    });
+}
+
+pub fn disable_periodic_interrupt() {
+    interrupts::without_interrupts(|| {
+        unsafe { crate::interrupts::PICS.lock().mask(RTC_IRQ) };
+        let mut cmos = CMOS.lock();
+        unsafe {
+            let status_b = cmos.read(REG_STATUS_B);
+            cmos.write(REG_STATUS_B, status_b & !STATUS_B_PERIODIC_INTERRUPT);
+        }
+        FREQUENCY.store(0, Ordering::Relaxed);
+    });
+}
+
+// number of periodic interrupts received so far
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// frequency of the periodic interrupt in Hz, 0 if disabled
+pub fn frequency() -> u32 {
+    FREQUENCY.load(Ordering::Relaxed)
+}
+
+// Called by the RTC interrupt handler
+pub(crate) fn handle_interrupt() {
+    // status register C must be read to acknowledge the interrupt
+    unsafe { CMOS.lock().read(REG_STATUS_C) };
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test_case]
+fn test_decode_bcd_12_hour() {
+    // 2024-02-29 12:30:05 AM in BCD and 12-hour mode
+    let raw = [0x05, 0x30, 0x12, 0x29, 0x02, 0x24, 0x20];
+    let time = decode(raw, 0);
+    assert_eq!(time, DateTime { year: 2024, month: 2, day: 29, hour: 0, minute: 30, second: 5 });
+    // 11 PM
+    let time = decode([0, 0, HOUR_PM | 0x11, 1, 1, 0, 0x20], 0);
+    assert_eq!(time.hour, 23);
+}
+
+#[test_case]
+fn test_decode_binary_24_hour() {
+    let raw = [59, 59, 23, 31, 12, 99, 20];
+    let time = decode(raw, STATUS_B_BINARY | STATUS_B_24_HOUR);
+    assert_eq!(time, DateTime { year: 2099, month: 12, day: 31, hour: 23, minute: 59, second: 59 });
+}
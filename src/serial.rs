@@ -4,12 +4,21 @@ use uart_16550::SerialPort;
 use spin::Mutex;
 use lazy_static::lazy_static;
 use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+const REG_INTERRUPT_ENABLE: u16 = COM1 + 1;
+const REG_LINE_STATUS: u16 = COM1 + 5;
+const INTERRUPT_DATA_AVAILABLE: u8 = 0x01;
+const LINE_STATUS_DATA_READY: u8 = 0x01;
+// the first serial port sits on IRQ 4
+const COM1_IRQ: u8 = 4;
 
 // same as vga buffer
 lazy_static! {
     pub static ref SERIAL1: Mutex<SerialPort> = {
         let mut serial_port = unsafe {
-            SerialPort::new(0x3F8) // 0x3F8: standard port number for the first serial interface
+            SerialPort::new(COM1) // 0x3F8: standard port number for the first serial interface
         };
         serial_port.init();
         Mutex::new(serial_port)
@@ -28,6 +37,38 @@ pub fn _print(args: ::core::fmt::Arguments) {
     });
 }
 
+// raise IRQ 4 whenever a byte arrives on the first serial port
+pub fn enable_receive_interrupt() {
+    // initializing the port later would overwrite the interrupt enable register
+    lazy_static::initialize(&SERIAL1);
+    interrupts::without_interrupts(|| unsafe {
+        Port::<u8>::new(REG_INTERRUPT_ENABLE).write(INTERRUPT_DATA_AVAILABLE);
+        crate::interrupts::PICS.lock().unmask(COM1_IRQ);
+    });
+}
+
+// the next received byte, None if there is none
+// Called by the serial interrupt handler, receiving does not need SERIAL1
+pub(crate) fn try_receive() -> Option<u8> {
+    unsafe {
+        if Port::<u8>::new(REG_LINE_STATUS).read() & LINE_STATUS_DATA_READY == 0 {
+            return None;
+        }
+        Some(Port::<u8>::new(COM1).read())
+    }
+}
+
+// Writes to the serial port without taking SERIAL1, for NMI and watchdog
+// dumps where the lock may be held by the very code that got stuck
+#[doc(hidden)]
+pub fn _emergency_print(args: ::core::fmt::Arguments) {
+    use core::fmt::Write;
+
+    // the port was already initialized through SERIAL1
+    let mut serial_port = unsafe { SerialPort::new(COM1) };
+    let _ = serial_port.write_fmt(args);
+}
+
 // Prints to the host through the serial interface.
 #[macro_export]
 macro_rules! serial_print {
@@ -45,3 +86,11 @@ macro_rules! serial_println {
         concat!($fmt, "\n"), $($arg)*));
 }
 
+// Prints to the host through the serial interface, bypassing the SERIAL1 lock.
+#[macro_export]
+macro_rules! emergency_println {
+    () => ($crate::serial::_emergency_print(format_args!("\n")));
+    ($fmt:expr) => ($crate::serial::_emergency_print(format_args!(concat!($fmt, "\n"))));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial::_emergency_print(
+        format_args!(concat!($fmt, "\n"), $($arg)*)));
+}
@@ -0,0 +1,136 @@
+/* the commands the shell starts with */
+
+use super::{Command, Output};
+use crate::task::{stats, work_stealing, Priority};
+use crate::vga_buffer::Color;
+use crate::{allocator, interrupts, memory, time};
+use core::fmt::{self, Write};
+use x86_64::instructions::{interrupts as cpu_interrupts, port::Port};
+
+pub(super) const ALL: &[Command] = &[
+    Command { name: "help", help: "list the commands", handler: help },
+    Command { name: "mem", help: "heap and physical frame usage", handler: mem },
+    Command { name: "uptime", help: "time since boot", handler: uptime },
+    Command { name: "irq", help: "interrupt counters", handler: irq },
+    Command { name: "tasks", help: "scheduling statistics of the tasks", handler: tasks },
+    Command { name: "clear", help: "clear the screen", handler: clear },
+    Command { name: "color", help: "color <fg> [bg]: set the text colors", handler: color },
+    Command { name: "reboot", help: "restart the machine", handler: reboot },
+    Command { name: "panic", help: "panic the kernel", handler: panic },
+];
+
+fn help(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    for command in super::commands() {
+        writeln!(out, "{:<8} {}", command.name, command.help)?;
+    }
+    Ok(())
+}
+
+fn mem(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    let heap = allocator::stats();
+    writeln!(out, "heap:   {} of {} KiB used, {} allocations",
+        heap.used / 1024, heap.size / 1024, heap.allocations)?;
+    let frames = memory::frame_stats();
+    writeln!(out, "frames: {} of {} used ({} KiB free)",
+        frames.used, frames.total, (frames.total - frames.used) * 4)
+}
+
+fn uptime(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    let uptime = time::uptime();
+    let secs = uptime.as_secs();
+    writeln!(out, "up {}:{:02}:{:02}.{:03}",
+        secs / 3600, secs / 60 % 60, secs % 60, uptime.subsec_millis())
+}
+
+fn irq(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    write!(out, "{}", interrupts::stats::table())
+}
+
+fn tasks(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    writeln!(out, "{} tasks", work_stealing::task_count())?;
+    writeln!(out, "{:>10} {:>14}  {:<8}  {}", "POLLS", "CYCLES", "PRIORITY", "ID")?;
+    for (id, stats) in stats::snapshot() {
+        // derived Debug ignores the width, so it goes last
+        let priority = match stats.priority {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        };
+        writeln!(out, "{:>10} {:>14}  {:<8}  {:?}", stats.polls, stats.cycles, priority, id)?;
+    }
+    Ok(())
+}
+
+fn clear(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    cpu_interrupts::without_interrupts(|| out.writer().lock().clear());
+    Ok(())
+}
+
+fn color(out: &mut Output, args: &[&str]) -> fmt::Result {
+    let parsed = match args {
+        [fg] => parse_color(fg).map(|fg| (fg, Color::Black)),
+        [fg, bg] => parse_color(fg).zip(parse_color(bg)),
+        _ => None,
+    };
+    match parsed {
+        Some((fg, bg)) => {
+            cpu_interrupts::without_interrupts(|| out.writer().lock().set_colors(fg, bg));
+            Ok(())
+        }
+        None => writeln!(out, "usage: color <fg> [bg], colors: {}", COLOR_NAMES.join(" ")),
+    }
+}
+
+const COLOR_NAMES: [&str; 16] = [
+    "black", "blue", "green", "cyan", "red", "magenta", "brown", "lightgray",
+    "darkgray", "lightblue", "lightgreen", "lightcyan", "lightred", "pink", "yellow", "white",
+];
+
+fn parse_color(name: &str) -> Option<Color> {
+    COLOR_NAMES.iter()
+        .position(|color| color.eq_ignore_ascii_case(name))
+        .map(|index| Color::from_index(index as u8))
+}
+
+// how often to poll the keyboard controller, and how long to give the reset
+const RESET_POLLS: usize = 0x10000;
+const RESET_WAIT_MS: u64 = 100;
+
+fn reboot(out: &mut Output, _args: &[&str]) -> fmt::Result {
+    writeln!(out, "rebooting")?;
+    cpu_interrupts::disable();
+    unsafe {
+        // pulse the reset line through the keyboard controller
+        // without a controller the port reads 0xFF, so don't wait forever
+        let mut status = Port::<u8>::new(0x64);
+        for _ in 0..RESET_POLLS {
+            if status.read() & 0x02 == 0 { // input buffer empty
+                status.write(0xFE);
+                break;
+            }
+        }
+        crate::time::delay_ms(RESET_WAIT_MS);
+
+        // no controller, or it ignored us: triple fault with an empty IDT
+        let idt = x86_64::structures::DescriptorTablePointer {
+            limit: 0,
+            base: x86_64::VirtAddr::new(0),
+        };
+        x86_64::instructions::tables::lidt(&idt);
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
+
+fn panic(_out: &mut Output, args: &[&str]) -> fmt::Result {
+    if args.is_empty() {
+        panic!("panic requested from the shell");
+    }
+    panic!("{}", args.join(" "));
+}
+
+#[test_case]
+fn test_parse_color() {
+    assert_eq!(parse_color("LightRed"), Some(Color::LightRed));
+    assert_eq!(parse_color("black"), Some(Color::Black));
+    assert_eq!(parse_color("purple"), None);
+}
@@ -0,0 +1,141 @@
+/* the kernel shell: reads command lines and runs registered commands */
+
+mod builtins;
+
+use crate::serial::SERIAL1;
+use crate::task::line_editor;
+use crate::vga_buffer::{console, Writer};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::{self, Write};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+pub const PROMPT: &str = "> ";
+
+// a command gets its arguments without the command name
+pub type Handler = fn(&mut Output, &[&str]) -> fmt::Result;
+
+#[derive(Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub handler: Handler,
+}
+
+lazy_static! {
+    // sorted by name, which is the order help lists them in
+    static ref COMMANDS: Mutex<BTreeMap<&'static str, Command>> = {
+        let mut commands = BTreeMap::new();
+        for command in builtins::ALL {
+            commands.insert(command.name, *command);
+        }
+        Mutex::new(commands)
+    };
+}
+
+// add a command, returns false if the name is taken
+pub fn register(name: &'static str, help: &'static str, handler: Handler) -> bool {
+    let mut commands = COMMANDS.lock();
+    if commands.contains_key(name) {
+        return false;
+    }
+    commands.insert(name, Command { name, help, handler });
+    true
+}
+
+pub fn commands() -> Vec<Command> {
+    COMMANDS.lock().values().copied().collect()
+}
+
+fn find(name: &str) -> Option<Command> {
+    COMMANDS.lock().get(name).copied()
+}
+
+// where commands print to: the shell's console and, for the shell on
+// SERIAL_CONSOLE, a terminal on COM1
+pub struct Output {
+    console: usize,
+}
+
+impl Output {
+    pub fn new() -> Output {
+        Output::on(0)
+    }
+
+    pub fn on(console: usize) -> Output {
+        Output { console }
+    }
+
+    pub fn console(&self) -> usize {
+        self.console
+    }
+
+    // the writer of the shell's console, for commands that change more than the text
+    pub fn writer(&self) -> &'static Mutex<Writer> {
+        console::console(self.console)
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::new()
+    }
+}
+
+impl fmt::Write for Output {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        interrupts::without_interrupts(|| {
+            self.writer().lock().write_string(s);
+            if self.console != console::SERIAL_CONSOLE {
+                return Ok(());
+            }
+            let mut serial = SERIAL1.lock();
+            // terminals need a carriage return to get back to the first column
+            for (i, part) in s.split('\n').enumerate() {
+                if i > 0 {
+                    serial.write_str("\r\n")?;
+                }
+                serial.write_str(part)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// run one command line on 'console', words are separated by whitespace
+pub fn execute(console: usize, line: &str) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (name, args) = match words.split_first() {
+        Some((name, args)) => (*name, args),
+        None => return,
+    };
+    let mut out = Output::on(console);
+    // the lock is released before running, so commands may register others
+    let result = match find(name) {
+        Some(command) => (command.handler)(&mut out, args),
+        None => writeln!(out, "unknown command: {} (try 'help')", name),
+    };
+    if result.is_err() {
+        let _ = writeln!(out, "{}: failed", name);
+    }
+}
+
+// the shell task of 'console': prompt, read a line, run it, forever
+pub async fn run(console: usize) {
+    loop {
+        let line = line_editor::read_line(console, PROMPT).await;
+        execute(console, &line);
+    }
+}
+
+#[test_case]
+fn test_output_goes_to_its_console() {
+    let mut out = Output::on(3);
+    write!(out, "on three").unwrap();
+    interrupts::without_interrupts(|| {
+        let writer = out.writer().lock();
+        let (row, _) = writer.position();
+        assert_eq!(writer.row_text(row), "on three");
+    });
+}
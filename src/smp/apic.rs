@@ -0,0 +1,141 @@
+/* local APIC: identify the current CPU and send inter-processor interrupts */
+
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::PhysAddr;
+use x86_64::instructions::interrupts;
+use crate::{memory, time};
+
+// registers, as offsets from the base address
+const REG_ID: u64 = 0x20;
+const REG_EOI: u64 = 0xb0;
+const REG_SPURIOUS: u64 = 0xf0;
+const REG_ICR_LOW: u64 = 0x300;
+const REG_ICR_HIGH: u64 = 0x310;
+const REG_LVT_TIMER: u64 = 0x320;
+const REG_TIMER_INITIAL: u64 = 0x380;
+const REG_TIMER_CURRENT: u64 = 0x390;
+const REG_TIMER_DIVIDE: u64 = 0x3e0;
+
+const SPURIOUS_APIC_ENABLE: u32 = 1 << 8;
+const ICR_DELIVERY_NMI: u32 = 0b100 << 8;
+const ICR_DELIVERY_INIT: u32 = 0b101 << 8;
+const ICR_DELIVERY_STARTUP: u32 = 0b110 << 8;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+const LVT_MASKED: u32 = 1 << 16;
+const TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_16: u32 = 0b0011;
+
+// how long the timer counts down while it is calibrated
+const TIMER_CALIBRATION_MS: u64 = 10;
+
+// vector of the interrupt the APIC raises when a request went away
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+// sent to a halted CPU when it has new work
+pub const WAKEUP_VECTOR: u8 = 0xf0;
+// the periodic local timer every CPU checks the watchdog on
+pub const WATCHDOG_VECTOR: u8 = 0xf1;
+
+// virtual address of the registers, 0 before init
+static BASE: AtomicU64 = AtomicU64::new(0);
+// timer counts per millisecond at divide 16, 0 before calibrate_timer
+static TIMER_PER_MS: AtomicU64 = AtomicU64::new(0);
+
+pub(super) fn init(address: PhysAddr) {
+    BASE.store(memory::phys_to_virt(address).as_u64(), Ordering::Relaxed);
+}
+
+pub fn is_available() -> bool {
+    BASE.load(Ordering::Relaxed) != 0
+}
+
+fn base() -> u64 {
+    let base = BASE.load(Ordering::Relaxed);
+    assert!(base != 0, "local APIC not initialized");
+    base
+}
+
+unsafe fn read(register: u64) -> u32 {
+    ptr::read_volatile((base() + register) as *const u32)
+}
+
+unsafe fn write(register: u64, value: u32) {
+    ptr::write_volatile((base() + register) as *mut u32, value)
+}
+
+// APIC id of the CPU running this code
+pub fn id() -> u8 {
+    (unsafe { read(REG_ID) } >> 24) as u8
+}
+
+// software enable the local APIC of the current CPU
+pub(crate) fn enable() {
+    unsafe {
+        let spurious = read(REG_SPURIOUS) & !0xff;
+        write(REG_SPURIOUS, spurious | SPURIOUS_APIC_ENABLE | u32::from(SPURIOUS_VECTOR));
+    }
+}
+
+// acknowledge an interrupt delivered by the local APIC
+pub(crate) fn end_of_interrupt() {
+    unsafe { write(REG_EOI, 0) };
+}
+
+// write the interrupt command register and wait until the APIC took it
+fn send(apic_id: u8, command: u32) {
+    // an interrupt handler sending an IPI in between would mix up the halves
+    interrupts::without_interrupts(|| unsafe {
+        write(REG_ICR_HIGH, u32::from(apic_id) << 24);
+        write(REG_ICR_LOW, command);
+        while read(REG_ICR_LOW) & ICR_DELIVERY_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+    });
+}
+
+// raise interrupt 'vector' on the processor with the given APIC id
+pub fn send_ipi(apic_id: u8, vector: u8) {
+    send(apic_id, ICR_LEVEL_ASSERT | u32::from(vector));
+}
+
+// reset a processor into its wait-for-SIPI state
+pub(super) fn send_init(apic_id: u8) {
+    send(apic_id, ICR_DELIVERY_INIT | ICR_LEVEL_ASSERT);
+}
+
+// start a processor in real mode at physical address page * 4096
+pub(super) fn send_startup(apic_id: u8, page: u8) {
+    send(apic_id, ICR_DELIVERY_STARTUP | ICR_LEVEL_ASSERT | u32::from(page));
+}
+
+// send a non-maskable interrupt, which arrives even with interrupts disabled
+pub fn send_nmi(apic_id: u8) {
+    send(apic_id, ICR_DELIVERY_NMI | ICR_LEVEL_ASSERT);
+}
+
+// measure the local timer's rate against the kernel clock, all CPUs share
+// the bus clock it counts, so the BSP does this once
+pub(crate) fn calibrate_timer() {
+    unsafe {
+        write(REG_TIMER_DIVIDE, TIMER_DIVIDE_16);
+        write(REG_LVT_TIMER, LVT_MASKED);
+        write(REG_TIMER_INITIAL, u32::MAX);
+    }
+    time::delay_ms(TIMER_CALIBRATION_MS);
+    let elapsed = u32::MAX - unsafe { read(REG_TIMER_CURRENT) };
+    unsafe { write(REG_TIMER_INITIAL, 0) };
+    TIMER_PER_MS.store(u64::from(elapsed) / TIMER_CALIBRATION_MS, Ordering::Relaxed);
+}
+
+// raise 'vector' on the current CPU every 'period_ms'
+pub(crate) fn start_periodic_timer(vector: u8, period_ms: u64) {
+    let per_ms = TIMER_PER_MS.load(Ordering::Relaxed);
+    assert!(per_ms != 0, "local APIC timer not calibrated");
+    let count = (per_ms * period_ms).min(u64::from(u32::MAX)) as u32;
+    unsafe {
+        write(REG_TIMER_DIVIDE, TIMER_DIVIDE_16);
+        write(REG_LVT_TIMER, TIMER_PERIODIC | u32::from(vector));
+        write(REG_TIMER_INITIAL, count);
+    }
+}
@@ -0,0 +1,86 @@
+/* Multiple APIC Description Table: the local APIC address and the list of processors */
+
+use alloc::vec::Vec;
+use core::mem;
+use x86_64::PhysAddr;
+use crate::acpi;
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+
+const LOCAL_APIC_ENABLED: u32 = 1 << 0;
+const LOCAL_APIC_ONLINE_CAPABLE: u32 = 1 << 1; // disabled now, but may be started
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct MadtHeader {
+    header: acpi::SdtHeader,
+    local_apic_address: u32,
+    flags: u32,
+}
+
+// every entry starts with its type and length
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct EntryHeader {
+    entry_type: u8,
+    length: u8,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct LocalApicEntry {
+    header: EntryHeader,
+    processor_id: u8,
+    apic_id: u8,
+    flags: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct LocalApicAddressOverride {
+    header: EntryHeader,
+    reserved: u16,
+    address: u64,
+}
+
+pub(super) struct Madt {
+    pub(super) local_apic: PhysAddr,
+    pub(super) apic_ids: Vec<u8>, // of all usable processors, the BSP included
+}
+
+// None if the firmware has no MADT, a uniprocessor system then
+pub(super) fn parse() -> Option<Madt> {
+    let addr = acpi::find_table(b"APIC")?;
+    let mut apic_ids = Vec::new();
+
+    unsafe {
+        let madt: MadtHeader = acpi::read(addr);
+        let mut local_apic = u64::from(madt.local_apic_address);
+        let end = addr + madt.header.length as usize;
+        let mut entry = addr + mem::size_of::<MadtHeader>();
+
+        while entry + mem::size_of::<EntryHeader>() <= end {
+            let header: EntryHeader = acpi::read(entry);
+            if header.length < 2 {
+                break; // broken table, don't loop forever
+            }
+            match header.entry_type {
+                ENTRY_LOCAL_APIC => {
+                    let cpu: LocalApicEntry = acpi::read(entry);
+                    if cpu.flags & (LOCAL_APIC_ENABLED | LOCAL_APIC_ONLINE_CAPABLE) != 0 {
+                        apic_ids.push(cpu.apic_id);
+                    }
+                }
+                ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE => {
+                    let over: LocalApicAddressOverride = acpi::read(entry);
+                    local_apic = over.address;
+                }
+                _ => {}
+            }
+            entry += usize::from(header.length);
+        }
+
+        Some(Madt { local_apic: PhysAddr::new(local_apic), apic_ids })
+    }
+}
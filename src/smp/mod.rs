@@ -0,0 +1,161 @@
+/* symmetric multiprocessing: find the processors in the MADT and start the application processors */
+
+pub mod apic;
+pub mod percpu;
+mod madt;
+mod trampoline;
+
+use alloc::{boxed::Box, vec};
+use core::hint;
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use x86_64::structures::paging::{
+    FrameAllocator, Mapper, Page, PageTableFlags, PhysFrame, Size4KiB,
+    mapper::MapToError,
+};
+use x86_64::registers::control::{Cr0, Cr3, Cr4};
+use x86_64::{PhysAddr, VirtAddr};
+use crate::{gdt, interrupts, time};
+use trampoline::Args;
+
+pub const MAX_CPUS: usize = 16;
+pub const AP_STACK_SIZE: usize = 4096 * 4;
+
+// how long to wait for a started processor to report in
+const STARTUP_TIMEOUT_MS: u64 = 100;
+
+// processors that finished their initialization, the BSP included
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+// APIC id of every processor, indexed by CPU number; the BSP is CPU 0
+const NO_CPU: AtomicU8 = AtomicU8::new(u8::MAX);
+static APIC_IDS: [AtomicU8; MAX_CPUS] = [NO_CPU; MAX_CPUS];
+// CR4 of the BSP, which the APs take over once they are in long mode
+static BSP_CR4: AtomicU64 = AtomicU64::new(0);
+
+// find and start all application processors
+// memory::init and time::init must have been called before, the mapper
+// identity maps the trampoline page
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) {
+    let madt = match madt::parse() {
+        Some(madt) => madt,
+        None => return, // no MADT, just the BSP
+    };
+    apic::init(madt.local_apic);
+    apic::enable();
+    apic::calibrate_timer();
+    let bsp = apic::id();
+    APIC_IDS[0].store(bsp, Ordering::Relaxed);
+    percpu::init(0, bsp);
+
+    identity_map_trampoline(mapper, frame_allocator);
+    unsafe { trampoline::install() };
+
+    let mut cpu = 1;
+    for &apic_id in madt.apic_ids.iter().filter(|&&id| id != bsp) {
+        if cpu == MAX_CPUS {
+            crate::println!("WARNING: more than {} CPUs, ignoring the rest", MAX_CPUS);
+            break;
+        }
+        if start_ap(cpu, apic_id) {
+            cpu += 1;
+        } else {
+            crate::println!("WARNING: CPU with APIC id {} did not start", apic_id);
+        }
+    }
+}
+
+// the AP enables paging while it runs at the trampoline's physical address
+fn identity_map_trampoline(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) {
+    let frame = PhysFrame::containing_address(PhysAddr::new(trampoline::ADDRESS));
+    let page = Page::containing_address(VirtAddr::new(trampoline::ADDRESS));
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    match unsafe { mapper.map_to(page, frame, flags, frame_allocator) } {
+        Ok(flush) => flush.flush(),
+        // the bootloader may have identity mapped low memory already
+        Err(MapToError::PageAlreadyMapped(mapped)) if mapped == frame => {}
+        Err(err) => panic!("failed to map the AP trampoline: {:?}", err),
+    }
+}
+
+// INIT-SIPI-SIPI, returns whether the processor came online
+fn start_ap(cpu: usize, apic_id: u8) -> bool {
+    // never freed, the CPU runs on it until it is turned off
+    let stack = Box::leak(vec![0u8; AP_STACK_SIZE].into_boxed_slice());
+    let stack_top = (stack.as_ptr() as u64 + AP_STACK_SIZE as u64) & !0xf;
+    let cr3 = Cr3::read().0.start_address().as_u64();
+    assert!(cr3 < 1 << 32, "the AP trampoline can't load page tables above 4 GiB");
+    let cr4 = Cr4::read_raw();
+    BSP_CR4.store(cr4, Ordering::Relaxed);
+    unsafe {
+        trampoline::set_args(Args {
+            cr3,
+            cr4: trampoline::trampoline_cr4(cr4),
+            cr0: Cr0::read_raw(),
+            stack_top,
+            entry: ap_main as usize as u64,
+            cpu: cpu as u64,
+        });
+    }
+    APIC_IDS[cpu].store(apic_id, Ordering::Relaxed);
+    let online = ONLINE.load(Ordering::Acquire);
+
+    apic::send_init(apic_id);
+    time::delay_ms(10);
+    // a second startup IPI is only needed if the first one was lost
+    for _ in 0..2 {
+        apic::send_startup(apic_id, (trampoline::ADDRESS >> 12) as u8);
+        if wait_online(online, STARTUP_TIMEOUT_MS) {
+            return true;
+        }
+    }
+    APIC_IDS[cpu].store(u8::MAX, Ordering::Relaxed);
+    false
+}
+
+fn wait_online(before: usize, timeout_ms: u64) -> bool {
+    let deadline = time::monotonic_nanos() + timeout_ms * 1_000_000;
+    while time::monotonic_nanos() < deadline {
+        if ONLINE.load(Ordering::Acquire) > before {
+            return true;
+        }
+        hint::spin_loop();
+    }
+    false
+}
+
+// first Rust code of an application processor, entered from the trampoline
+extern "C" fn ap_main(cpu: u64) -> ! {
+    unsafe { Cr4::write_raw(BSP_CR4.load(Ordering::Relaxed)) };
+    percpu::init(cpu as usize, apic::id());
+    gdt::init_ap();
+    interrupts::init_idt();
+    apic::enable();
+    // the BSP checks on the other CPUs from the PIT, an AP from its own timer
+    apic::start_periodic_timer(apic::WATCHDOG_VECTOR, crate::watchdog::CHECK_PERIOD_MS);
+    ONLINE.fetch_add(1, Ordering::Release);
+
+    // only the BSP receives device interrupts, the APs run tasks
+    crate::task::work_stealing::run()
+}
+
+// number of processors running the kernel
+pub fn cpu_count() -> usize {
+    ONLINE.load(Ordering::Acquire)
+}
+
+// number of the CPU running this code, 0 for the BSP
+pub fn current_cpu() -> usize {
+    // before smp::init only the BSP runs
+    percpu::current().map_or(0, |data| data.cpu())
+}
+
+// APIC id of CPU number 'cpu', None if it is not online
+pub fn apic_id(cpu: usize) -> Option<u8> {
+    let id = APIC_IDS.get(cpu)?.load(Ordering::Relaxed);
+    if cpu >= cpu_count() || id == u8::MAX { None } else { Some(id) }
+}
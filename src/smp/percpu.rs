@@ -0,0 +1,62 @@
+/* per-CPU data, reached through the GS base of each processor */
+
+use alloc::boxed::Box;
+use core::arch::asm;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use x86_64::VirtAddr;
+use x86_64::registers::model_specific::GsBase;
+
+// set once the BSP has its per-CPU data, every AP sets up its own
+// before running any other kernel code
+static READY: AtomicBool = AtomicBool::new(false);
+
+#[repr(C)]
+pub struct PerCpu {
+    self_ptr: *const PerCpu, // must stay at offset 0, read through gs:0
+    cpu: usize,
+    apic_id: u8,
+    // per-CPU state of other subsystems
+    pub(crate) poll_budget: AtomicU32,
+}
+
+// only this CPU writes the plain fields, and only before publishing them
+unsafe impl Sync for PerCpu {}
+
+impl PerCpu {
+    // CPU number, 0 for the BSP
+    pub fn cpu(&self) -> usize {
+        self.cpu
+    }
+
+    pub fn apic_id(&self) -> u8 {
+        self.apic_id
+    }
+}
+
+// allocate the data of the current CPU and point its GS base at it
+pub(super) fn init(cpu: usize, apic_id: u8) {
+    let data = Box::leak(Box::new(PerCpu {
+        self_ptr: ptr::null(),
+        cpu,
+        apic_id,
+        poll_budget: AtomicU32::new(crate::task::budget::UNCONSTRAINED),
+    }));
+    data.self_ptr = data;
+    GsBase::write(VirtAddr::from_ptr(data));
+    if cpu == 0 {
+        READY.store(true, Ordering::Release);
+    }
+}
+
+// data of the CPU running this code, None before smp::init
+pub fn current() -> Option<&'static PerCpu> {
+    if !READY.load(Ordering::Acquire) {
+        return None;
+    }
+    let data: *const PerCpu;
+    unsafe {
+        asm!("mov {}, gs:[0]", out(reg) data, options(nostack, preserves_flags, readonly));
+        Some(&*data)
+    }
+}
@@ -0,0 +1,97 @@
+/* real-mode entry of the application processors, copied below 1 MiB */
+
+use core::arch::global_asm;
+use core::ptr;
+use x86_64::PhysAddr;
+use x86_64::registers::control::Cr4Flags;
+use crate::memory::phys_to_virt;
+
+// a startup IPI starts a processor in real mode at a page below 1 MiB
+pub(super) const ADDRESS: u64 = 0x8000;
+// where the BSP leaves the Args for the next processor
+const ARGS_ADDRESS: u64 = ADDRESS + 0xf00;
+
+// the CR4 bits the trampoline sets, the AP sets the others of the BSP once
+// it is in long mode; PCIDE, for one, raises #GP while long mode is inactive
+pub(super) fn trampoline_cr4(cr4: u64) -> u64 {
+    let early = Cr4Flags::PHYSICAL_ADDRESS_EXTENSION | Cr4Flags::PAGE_GLOBAL | Cr4Flags::OSFXSR;
+    cr4 & early.bits()
+}
+
+// read by the trampoline, the offsets are hard coded in the assembly below
+#[repr(C)]
+pub(super) struct Args {
+    pub(super) cr3: u64, // below 4 GiB, loaded in 32-bit code
+    pub(super) cr4: u64, // see trampoline_cr4
+    pub(super) cr0: u64,
+    pub(super) stack_top: u64,
+    pub(super) entry: u64, // extern "C" fn(cpu: u64) -> !
+    pub(super) cpu: u64,
+}
+
+// Enters long mode directly from real mode: the paging registers get the
+// values of the BSP, so the AP shares its page tables and continues at
+// 'entry' on its own stack. The code runs at ADDRESS instead of where it
+// was linked, so all addresses are computed relative to it.
+global_asm!(
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_end",
+    ".code16",
+    "ap_trampoline_start:",
+    "    cli",
+    "    cld",
+    "    xorw %ax, %ax",
+    "    movw %ax, %ds",
+    "    lgdtl 0x8000 + (ap_trampoline_gdt_ptr - ap_trampoline_start)",
+    "    movl 0x8f08, %eax", // cr4, PAE enabled
+    "    movl %eax, %cr4",
+    "    movl 0x8f00, %eax", // cr3, the page tables must lie below 4 GiB
+    "    movl %eax, %cr3",
+    "    movl $0xc0000080, %ecx", // EFER
+    "    rdmsr",
+    "    orl $0x900, %eax", // long mode and no-execute enable
+    "    wrmsr",
+    "    movl 0x8f10, %eax", // cr0, protection and paging at once
+    "    movl %eax, %cr0",
+    "    ljmpl $0x8, $(0x8000 + (ap_trampoline_long_mode - ap_trampoline_start))",
+    ".code64",
+    "ap_trampoline_long_mode:",
+    "    xorw %ax, %ax",
+    "    movw %ax, %ds",
+    "    movw %ax, %es",
+    "    movw %ax, %ss",
+    "    movq 0x8f18, %rsp",
+    "    movq 0x8f28, %rdi",
+    "    movq 0x8f20, %rax",
+    "    callq *%rax",
+    "    ud2",
+    ".balign 8",
+    "ap_trampoline_gdt:",
+    "    .quad 0",
+    "    .quad 0x00af9a000000ffff", // 64-bit kernel code
+    "ap_trampoline_gdt_ptr:",
+    "    .word ap_trampoline_gdt_ptr - ap_trampoline_gdt - 1",
+    "    .long 0x8000 + (ap_trampoline_gdt - ap_trampoline_start)",
+    "ap_trampoline_end:",
+    options(att_syntax),
+);
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+}
+
+// copy the trampoline to ADDRESS, which must be identity mapped and unused
+pub(super) unsafe fn install() {
+    let start = ptr::addr_of!(ap_trampoline_start);
+    let len = ptr::addr_of!(ap_trampoline_end) as usize - start as usize;
+    assert!(ADDRESS + len as u64 <= ARGS_ADDRESS, "AP trampoline too large");
+    let target = phys_to_virt(PhysAddr::new(ADDRESS)).as_mut_ptr::<u8>();
+    ptr::copy_nonoverlapping(start, target, len);
+}
+
+// hand the arguments to the next processor started
+pub(super) unsafe fn set_args(args: Args) {
+    let target = phys_to_virt(PhysAddr::new(ARGS_ADDRESS)).as_mut_ptr::<Args>();
+    ptr::write_volatile(target, args);
+}
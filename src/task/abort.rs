@@ -0,0 +1,62 @@
+/* AbortHandle: cancel a task that is already owned by an executor */
+
+use super::{Task, TaskId};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures_util::task::AtomicWaker;
+
+// shared between a task and its AbortHandles
+pub(crate) struct AbortState {
+    aborted: AtomicBool,
+    waker: AtomicWaker, // the task's own waker, so aborting reschedules it
+}
+
+impl AbortState {
+    pub(crate) fn new() -> Self {
+        AbortState {
+            aborted: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AbortHandle {
+    task_id: TaskId,
+    state: Arc<AbortState>,
+}
+
+impl AbortHandle {
+    // mark the task cancelled, the executor drops its future the next time
+    // it is scheduled and its JoinHandle resolves to JoinError::Cancelled
+    pub fn abort(&self) {
+        self.state.aborted.store(true, Ordering::Release);
+        self.state.waker.wake();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state.aborted.load(Ordering::Acquire)
+    }
+
+    pub fn task_id(&self) -> TaskId {
+        self.task_id
+    }
+}
+
+impl Task {
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle {
+            task_id: self.id,
+            state: self.abort.clone(),
+        }
+    }
+
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.abort.aborted.load(Ordering::Acquire)
+    }
+
+    // remember the waker that reschedules this task in its executor
+    pub(crate) fn register_waker(&self, waker: &core::task::Waker) {
+        self.abort.waker.register(waker);
+    }
+}
@@ -0,0 +1,72 @@
+/* cooperative scheduling: a per-poll budget and yield_now */
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+
+// how many times a task may find a resource ready within one poll
+pub const POLL_BUDGET: u32 = 64;
+
+// budget outside of an executor poll, e.g. in tests using now_or_never
+pub(crate) const UNCONSTRAINED: u32 = u32::MAX;
+
+// used until the per-CPU data is set up
+static BUDGET: AtomicU32 = AtomicU32::new(UNCONSTRAINED);
+
+// every CPU polls its own task, so each has its own budget
+fn budget() -> &'static AtomicU32 {
+    match crate::smp::percpu::current() {
+        Some(cpu) => &cpu.poll_budget,
+        None => &BUDGET,
+    }
+}
+
+// Called by the executor around every poll
+pub(crate) fn reset() {
+    budget().store(POLL_BUDGET, Ordering::Relaxed);
+}
+
+pub(crate) fn unconstrain() {
+    budget().store(UNCONSTRAINED, Ordering::Relaxed);
+}
+
+// take one unit of budget before handing out a ready value
+// once it is used up the task is woken again and has to return Pending,
+// so a task that always finds its channel full can't hog the executor
+pub fn consume(cx: &mut Context) -> Poll<()> {
+    let budget = budget();
+    // interrupt handlers don't poll, so nobody else touches this CPU's budget
+    let remaining = budget.load(Ordering::Relaxed);
+    if remaining == UNCONSTRAINED {
+        return Poll::Ready(());
+    }
+    if remaining == 0 {
+        cx.waker().wake_by_ref();
+        return Poll::Pending;
+    }
+    budget.store(remaining - 1, Ordering::Relaxed);
+    Poll::Ready(())
+}
+
+// give the other ready tasks a turn
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref(); // back to the end of the queue
+        Poll::Pending
+    }
+}
@@ -0,0 +1,207 @@
+/* broadcast: every receiver gets a clone of every value sent after it subscribed */
+
+use super::Locked;
+use crate::task::budget;
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    Closed,      // all senders are gone and everything was received
+    Lagged(u64), // the receiver fell behind and missed this many values
+}
+
+struct Inner<T> {
+    buffer: VecDeque<T>, // the last 'capacity' values
+    capacity: usize,
+    first: u64, // sequence number of buffer[0]
+    senders: usize,
+    receivers: usize,
+    next_id: u64, // of the next receiver
+    wakers: Vec<(u64, Waker)>, // at most one per receiver, by receiver id
+}
+
+impl<T> Inner<T> {
+    fn end(&self) -> u64 {
+        self.first + self.buffer.len() as u64
+    }
+}
+
+// a channel keeping the last 'capacity' values for slow receivers
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must not be zero");
+    let inner = Arc::new(Locked::new(Inner {
+        buffer: VecDeque::with_capacity(capacity),
+        capacity,
+        first: 0,
+        senders: 1,
+        receivers: 1,
+        next_id: 1,
+        wakers: Vec::new(),
+    }));
+    (Sender { inner: inner.clone() }, Receiver { inner, id: 0, next: 0 })
+}
+
+pub struct Sender<T> {
+    inner: Arc<Locked<Inner<T>>>,
+}
+
+impl<T: Clone> Sender<T> {
+    // returns how many receivers will see the value
+    // a value nobody is subscribed to is dropped
+    pub fn send(&self, value: T) -> usize {
+        let (receivers, wakers) = self.inner.with(|inner| {
+            if inner.receivers == 0 {
+                return (0, Vec::new());
+            }
+            inner.buffer.push_back(value);
+            if inner.buffer.len() > inner.capacity {
+                inner.buffer.pop_front();
+                inner.first += 1;
+            }
+            (inner.receivers, mem::take(&mut inner.wakers))
+        });
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+        receivers
+    }
+
+    // a new receiver that gets values sent from now on
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (id, next) = self.inner.with(|inner| {
+            inner.receivers += 1;
+            inner.next_id += 1;
+            (inner.next_id - 1, inner.end())
+        });
+        Receiver { inner: self.inner.clone(), id, next }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.inner.with(|inner| inner.receivers)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.with(|inner| inner.senders += 1);
+        Sender { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers = self.inner.with(|inner| {
+            inner.senders -= 1;
+            if inner.senders == 0 { mem::take(&mut inner.wakers) } else { Vec::new() }
+        });
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    inner: Arc<Locked<Inner<T>>>,
+    id: u64, // finds the receiver's waker
+    next: u64, // sequence number of the next value to receive
+}
+
+impl<T: Clone> Receiver<T> {
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { receiver: self }
+    }
+
+    // None if no value is available yet
+    pub fn try_recv(&mut self) -> Option<Result<T, RecvError>> {
+        let next = &mut self.next;
+        self.inner.with(|inner| {
+            if *next < inner.first {
+                // skip what was overwritten and report it
+                let missed = inner.first - *next;
+                *next = inner.first;
+                Some(Err(RecvError::Lagged(missed)))
+            } else if *next < inner.end() {
+                let value = inner.buffer[(*next - inner.first) as usize].clone();
+                *next += 1;
+                Some(Ok(value))
+            } else if inner.senders == 0 {
+                Some(Err(RecvError::Closed))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Result<T, RecvError>> {
+        if budget::consume(cx).is_pending() {
+            return Poll::Pending;
+        }
+        if let Some(result) = self.try_recv() {
+            return Poll::Ready(result);
+        }
+        // a receiver polled again before the next send replaces its waker,
+        // so the list can't grow beyond the receivers
+        let id = self.id;
+        let replaced = self.inner.with(|inner| {
+            match inner.wakers.iter_mut().find(|(waiter, _)| *waiter == id) {
+                Some((_, waker)) if waker.will_wake(cx.waker()) => None,
+                Some((_, waker)) => Some(mem::replace(waker, cx.waker().clone())),
+                None => {
+                    inner.wakers.push((id, cx.waker().clone()));
+                    None
+                }
+            }
+        });
+        // dropping a waker may drop its task, not under the lock
+        drop(replaced);
+        match self.try_recv() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let id = self.id;
+        // the waker would keep the receiving task alive until the next send
+        let waker = self.inner.with(|inner| {
+            inner.receivers -= 1;
+            let index = inner.wakers.iter().position(|(waiter, _)| *waiter == id)?;
+            Some(inner.wakers.swap_remove(index))
+        });
+        drop(waker);
+    }
+}
+
+pub struct RecvFuture<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T: Clone> Future for RecvFuture<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[test_case]
+fn test_repeated_polls_keep_one_waker() {
+    use futures_util::task::noop_waker_ref;
+
+    let (sender, mut receiver) = channel::<u32>(4);
+    let mut other = sender.subscribe();
+    let mut cx = Context::from_waker(noop_waker_ref());
+    for _ in 0..10 {
+        assert!(receiver.poll_recv(&mut cx).is_pending());
+        assert!(other.poll_recv(&mut cx).is_pending());
+    }
+    assert_eq!(sender.inner.with(|inner| inner.wakers.len()), 2);
+    drop(other);
+    assert_eq!(sender.inner.with(|inner| inner.wakers.len()), 1);
+}
@@ -0,0 +1,41 @@
+/* channels between tasks: mpsc (bounded and unbounded), oneshot and broadcast */
+
+pub mod mpsc;
+pub mod oneshot;
+pub mod broadcast;
+
+use x86_64::instructions::interrupts;
+
+// channel state behind a spinlock that is never held while an interrupt
+// handler could try to take it too
+struct Locked<T> {
+    inner: spin::Mutex<T>,
+}
+
+impl<T> Locked<T> {
+    fn new(inner: T) -> Self {
+        Locked {
+            inner: spin::Mutex::new(inner),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        interrupts::without_interrupts(|| f(&mut self.inner.lock()))
+    }
+}
+
+// the value could not be sent because all receivers are gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Closed, // all senders are gone and nothing is left
+}
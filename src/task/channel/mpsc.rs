@@ -0,0 +1,253 @@
+/* multi-producer single-consumer queue, bounded or unbounded */
+
+use super::{Locked, SendError, TryRecvError, TrySendError};
+use crate::task::budget;
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use futures_util::stream::Stream;
+
+struct Inner<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>, // None for unbounded channels
+    senders: usize,
+    receiver_alive: bool,
+    receiver_waker: Option<Waker>,
+    sender_wakers: Vec<Waker>, // senders waiting for free capacity
+}
+
+impl<T> Inner<T> {
+    fn is_full(&self) -> bool {
+        self.capacity.map_or(false, |capacity| self.queue.len() >= capacity)
+    }
+
+    // queue a value and return the receiver to wake
+    fn push(&mut self, value: T) -> Option<Waker> {
+        self.queue.push_back(value);
+        self.receiver_waker.take()
+    }
+}
+
+type Chan<T> = Arc<Locked<Inner<T>>>;
+
+fn new_chan<T>(capacity: Option<usize>) -> Chan<T> {
+    Arc::new(Locked::new(Inner {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        receiver_alive: true,
+        receiver_waker: None,
+        sender_wakers: Vec::new(),
+    }))
+}
+
+// a channel holding at most 'capacity' values, sending waits while it is full
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must not be zero");
+    let chan = new_chan(Some(capacity));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+// a channel that grows on the heap, sending never waits
+pub fn unbounded<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = new_chan(None);
+    (UnboundedSender { chan: chan.clone() }, Receiver { chan })
+}
+
+fn clone_sender<T>(chan: &Chan<T>) -> Chan<T> {
+    chan.with(|inner| inner.senders += 1);
+    chan.clone()
+}
+
+fn drop_sender<T>(chan: &Chan<T>) {
+    let waker = chan.with(|inner| {
+        inner.senders -= 1;
+        if inner.senders == 0 { inner.receiver_waker.take() } else { None }
+    });
+    // the receiver has to notice the channel closed
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+pub struct Sender<T> {
+    chan: Chan<T>,
+}
+
+impl<T> Sender<T> {
+    // wait for free capacity and queue the value
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture { sender: self, value: Some(value) }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let result = self.chan.with(|inner| {
+            if !inner.receiver_alive {
+                Err(TrySendError::Closed(value))
+            } else if inner.is_full() {
+                Err(TrySendError::Full(value))
+            } else {
+                Ok(inner.push(value))
+            }
+        });
+        result.map(|waker| if let Some(waker) = waker { waker.wake() })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.with(|inner| !inner.receiver_alive)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender { chan: clone_sender(&self.chan) }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+pub struct SendFuture<'a, T> {
+    sender: &'a Sender<T>,
+    value: Option<T>,
+}
+
+// the value is never pinned, it is only moved into the queue
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let value = self.value.take().expect("SendFuture polled after completion");
+        match self.sender.try_send(value) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TrySendError::Closed(value)) => Poll::Ready(Err(SendError(value))),
+            Err(TrySendError::Full(value)) => {
+                // register, then retry in case the receiver made room meanwhile
+                self.sender.chan.with(|inner| {
+                    if !inner.sender_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        inner.sender_wakers.push(cx.waker().clone());
+                    }
+                });
+                match self.sender.try_send(value) {
+                    Ok(()) => Poll::Ready(Ok(())),
+                    Err(TrySendError::Closed(value)) => Poll::Ready(Err(SendError(value))),
+                    Err(TrySendError::Full(value)) => {
+                        self.value = Some(value);
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct UnboundedSender<T> {
+    chan: Chan<T>,
+}
+
+impl<T> UnboundedSender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let result = self.chan.with(|inner| {
+            if inner.receiver_alive { Ok(inner.push(value)) } else { Err(SendError(value)) }
+        });
+        result.map(|waker| if let Some(waker) = waker { waker.wake() })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.with(|inner| !inner.receiver_alive)
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        UnboundedSender { chan: clone_sender(&self.chan) }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+pub struct Receiver<T> {
+    chan: Chan<T>,
+}
+
+impl<T> Receiver<T> {
+    // the next value, None once all senders are gone and the queue is empty
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { receiver: self }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (result, wakers) = self.chan.with(|inner| match inner.queue.pop_front() {
+            Some(value) => (Ok(value), mem::take(&mut inner.sender_wakers)),
+            None if inner.senders == 0 => (Err(TryRecvError::Closed), Vec::new()),
+            None => (Err(TryRecvError::Empty), Vec::new()),
+        });
+        // there is room now for waiting senders
+        for waker in wakers {
+            waker.wake();
+        }
+        result
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        if budget::consume(cx).is_pending() {
+            return Poll::Pending;
+        }
+        match self.try_recv() {
+            Ok(value) => return Poll::Ready(Some(value)),
+            Err(TryRecvError::Closed) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => {}
+        }
+        self.chan.with(|inner| inner.receiver_waker = Some(cx.waker().clone()));
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let (queued, wakers) = self.chan.with(|inner| {
+            inner.receiver_alive = false;
+            (mem::take(&mut inner.queue), mem::take(&mut inner.sender_wakers))
+        });
+        // values may run arbitrary drop code, so drop them outside the lock
+        drop(queued);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+pub struct RecvFuture<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,103 @@
+/* oneshot: hand a single value from one task to another */
+
+use super::Locked;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+// the sender was dropped without sending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+struct Inner<T> {
+    value: Option<T>,
+    sender_alive: bool,
+    receiver_alive: bool,
+    waker: Option<Waker>,
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Locked::new(Inner {
+        value: None,
+        sender_alive: true,
+        receiver_alive: true,
+        waker: None,
+    }));
+    (Sender { inner: inner.clone() }, Receiver { inner })
+}
+
+pub struct Sender<T> {
+    inner: Arc<Locked<Inner<T>>>,
+}
+
+impl<T> Sender<T> {
+    // hands back the value if the receiver is gone
+    pub fn send(self, value: T) -> Result<(), T> {
+        // dropping 'self' at the end wakes the receiver
+        self.inner.with(|inner| {
+            if inner.receiver_alive {
+                inner.value = Some(value);
+                Ok(())
+            } else {
+                Err(value)
+            }
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.with(|inner| !inner.receiver_alive)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = self.inner.with(|inner| {
+            inner.sender_alive = false;
+            inner.waker.take()
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+// resolves to the sent value
+pub struct Receiver<T> {
+    inner: Arc<Locked<Inner<T>>>,
+}
+
+impl<T> Receiver<T> {
+    pub fn try_recv(&mut self) -> Option<Result<T, RecvError>> {
+        self.inner.with(|inner| match inner.value.take() {
+            Some(value) => Some(Ok(value)),
+            None if !inner.sender_alive => Some(Err(RecvError)),
+            None => None,
+        })
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.inner.with(|inner| match inner.value.take() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None if !inner.sender_alive => Poll::Ready(Err(RecvError)),
+            None => {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let value = self.inner.with(|inner| {
+            inner.receiver_alive = false;
+            inner.value.take()
+        });
+        drop(value);
+    }
+}
@@ -0,0 +1,184 @@
+use super::{budget, stats, Priority, Task, TaskId};
+use super::join::JoinHandle;
+use crate::time::tsc;
+use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+use core::future::Future;
+use core::task::{Context, Poll, Waker};
+use crossbeam_queue::ArrayQueue;
+
+// capacity of each wake queue, wakers push into it from interrupt handlers
+const TASK_QUEUE_SIZE: usize = 100;
+
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Task>,
+    // IDs of tasks that are ready to be polled, one queue per priority
+    task_queues: [Arc<ArrayQueue<TaskId>>; Priority::ALL.len()],
+    waker_cache: BTreeMap<TaskId, Waker>, // reuse a task's waker instead of allocating one per poll
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queues: Priority::ALL.map(|_| Arc::new(ArrayQueue::new(TASK_QUEUE_SIZE))),
+            waker_cache: BTreeMap::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, task: Task) {
+        let task_id = task.id;
+        let priority = task.priority;
+        if self.tasks.insert(task.id, task).is_some() {
+            panic!("task with same ID already in tasks");
+        }
+        self.task_queues[priority as usize].push(task_id).expect("task queue full");
+    }
+
+    // spawn a future and get a handle resolving to its output
+    pub fn spawn_with_handle<F>(&mut self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (task, handle) = Task::with_handle(future);
+        self.spawn(task);
+        handle
+    }
+
+    // number of tasks that have not completed yet
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn has_ready_tasks(&self) -> bool {
+        self.task_queues.iter().any(|queue| !queue.is_empty())
+    }
+
+    // poll ready tasks in weighted rounds until no queue has any left
+    fn run_ready_tasks(&mut self) {
+        loop {
+            let mut polled = false;
+            for priority in Priority::ALL {
+                for _ in 0..priority.weight() {
+                    match self.task_queues[priority as usize].pop() {
+                        Some(task_id) => {
+                            self.run_task(task_id);
+                            polled = true;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            if !polled {
+                break;
+            }
+        }
+    }
+
+    fn run_task(&mut self, task_id: TaskId) {
+        // destructure 'self' to avoid borrow checker errors
+        let Self {
+            tasks,
+            task_queues,
+            waker_cache,
+        } = self;
+
+        crate::watchdog::pet();
+        let task = match tasks.get_mut(&task_id) {
+            Some(task) => task,
+            None => return, // task no longer exists
+        };
+        if task.is_aborted() {
+            // dropping the future releases everything it owns
+            tasks.remove(&task_id);
+            waker_cache.remove(&task_id);
+            stats::remove(task_id);
+            return;
+        }
+        let priority = task.priority;
+        let waker = waker_cache.entry(task_id).or_insert_with(|| {
+            TaskWaker::new(task_id, task_queues[priority as usize].clone())
+        });
+        task.register_waker(waker);
+        let mut context = Context::from_waker(waker);
+
+        budget::reset();
+        let start = tsc::read();
+        let result = task.poll(&mut context);
+        stats::record(task_id, priority, tsc::read() - start);
+        budget::unconstrain();
+
+        if let Poll::Ready(()) = result {
+            // task done -> remove it and its cached waker
+            tasks.remove(&task_id);
+            waker_cache.remove(&task_id);
+            stats::remove(task_id);
+        }
+    }
+
+    // poll tasks until none of them is ready, then return instead of halting
+    pub fn run_until_idle(&mut self) {
+        while self.has_ready_tasks() {
+            self.run_ready_tasks();
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            self.sleep_if_idle();
+        }
+    }
+
+    // halt until the next interrupt if no task is ready
+    fn sleep_if_idle(&self) {
+        use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+        // an interrupt between the check and hlt could wake a task and then
+        // be slept through, so check with interrupts disabled and re-enable
+        // them atomically with hlt
+        interrupts::disable();
+        if !self.has_ready_tasks() {
+            enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        for task_id in self.tasks.keys() {
+            stats::remove(*task_id);
+        }
+    }
+}
+
+// wakes a task by pushing its ID into the wake queue of its priority
+struct TaskWaker {
+    task_id: TaskId,
+    task_queue: Arc<ArrayQueue<TaskId>>,
+}
+
+impl TaskWaker {
+    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task_id,
+            task_queue,
+        }))
+    }
+
+    fn wake_task(&self) {
+        self.task_queue.push(self.task_id).expect("task queue full");
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
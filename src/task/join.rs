@@ -0,0 +1,137 @@
+/* JoinHandle: wait for a spawned task and get its output */
+
+use super::Task;
+use super::abort::AbortHandle;
+use alloc::{boxed::Box, sync::Arc};
+use core::{future::Future, mem, pin::Pin};
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+// why a task did not produce its output
+// a panicking task can't be reported here: the kernel is built with
+// panic=abort, so a panic in any task halts the whole kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    Cancelled, // the task was dropped before it completed
+}
+
+enum State<T> {
+    Running,
+    Finished(Result<T, JoinError>),
+    Taken, // the JoinHandle already returned the result
+}
+
+// shared between the running task and its JoinHandle
+struct Shared<T> {
+    state: State<T>,
+    waker: Option<Waker>, // the task awaiting the JoinHandle
+}
+
+impl<T> Shared<T> {
+    // store the result unless there already is one, returns who to wake
+    fn complete(&mut self, result: Result<T, JoinError>) -> Option<Waker> {
+        if let State::Running = self.state {
+            self.state = State::Finished(result);
+        }
+        self.waker.take()
+    }
+}
+
+// the future stored in the Task, publishes its output for the JoinHandle
+struct Joinable<F: Future> {
+    future: Pin<Box<F>>,
+    shared: Arc<Mutex<Shared<F::Output>>>,
+}
+
+impl<F: Future> Joinable<F> {
+    fn complete(&self, result: Result<F::Output, JoinError>) {
+        let waker = self.shared.lock().complete(result);
+        // wake outside the lock, the waker may poll right away
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<F: Future> Future for Joinable<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        match self.future.as_mut().poll(cx) {
+            Poll::Ready(output) => {
+                self.complete(Ok(output));
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F: Future> Drop for Joinable<F> {
+    // a task dropped before finishing was cancelled
+    fn drop(&mut self) {
+        self.complete(Err(JoinError::Cancelled));
+    }
+}
+
+// resolves to the output of a spawned task
+// dropping the handle detaches the task, it keeps running
+pub struct JoinHandle<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    abort: AbortHandle,
+}
+
+impl<T> JoinHandle<T> {
+    // let the task run on without waiting for its output
+    pub fn detach(self) {}
+
+    // cancel the task, awaiting the handle then gives JoinError::Cancelled
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self.shared.lock().state, State::Running)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock();
+        match mem::replace(&mut shared.state, State::Taken) {
+            State::Finished(result) => Poll::Ready(result),
+            State::Running => {
+                shared.state = State::Running;
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            State::Taken => panic!("JoinHandle polled after completion"),
+        }
+    }
+}
+
+impl Task {
+    // create a task together with a handle to await its output
+    pub fn with_handle<F>(future: F) -> (Task, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared {
+            state: State::Running,
+            waker: None,
+        }));
+        let task = Task::new(Joinable {
+            future: Box::pin(future),
+            shared: shared.clone(),
+        });
+        let abort = task.abort_handle();
+        (task, JoinHandle { shared, abort })
+    }
+}
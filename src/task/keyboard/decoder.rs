@@ -0,0 +1,225 @@
+/* turning scancode bytes into key events with modifier and lock key state */
+
+use super::event::{KeyCode, KeyEvent, Modifiers};
+use super::layout::{self, DeadKey, Layout, Sym};
+use pc_keyboard::{layouts, HandleControl, KeyState, Keyboard, ScancodeSet1, ScancodeSet2};
+
+// bytes the keyboard answers commands with, they are not keys
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSet {
+    Set1, // what the PS/2 controller translates to by default
+    Set2, // what the keyboard itself sends
+}
+
+// pc-keyboard only splits the byte stream into key codes here, the layout
+// it is created with is never used
+enum Scanner {
+    Set1(Keyboard<layouts::Us104Key, ScancodeSet1>),
+    Set2(Keyboard<layouts::Us104Key, ScancodeSet2>),
+}
+
+pub struct Decoder {
+    scanner: Scanner,
+    modifiers: Modifiers,
+    locks_held: u8, // lock keys still down, their typematic repeats don't toggle
+    layout: Layout,
+    dead: Option<DeadKey>, // accent waiting for the next character
+}
+
+const CAPS_LOCK: u8 = 1 << 0;
+const NUM_LOCK: u8 = 1 << 1;
+const SCROLL_LOCK: u8 = 1 << 2;
+
+impl Decoder {
+    pub fn new(set: ScancodeSet) -> Decoder {
+        let scanner = match set {
+            ScancodeSet::Set1 => {
+                Scanner::Set1(Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore))
+            }
+            ScancodeSet::Set2 => {
+                Scanner::Set2(Keyboard::new(layouts::Us104Key, ScancodeSet2, HandleControl::Ignore))
+            }
+        };
+        Decoder {
+            scanner,
+            modifiers: Modifiers::default(),
+            locks_held: 0,
+            layout: Layout::Us,
+            dead: None,
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    // a pending dead key is dropped
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.dead = None;
+    }
+
+    // feed one byte from the keyboard, returns an event once it completes a
+    // scancode; invalid sequences are dropped
+    pub fn add_byte(&mut self, byte: u8) -> Option<KeyEvent> {
+        if byte == ACK || byte == RESEND {
+            return None;
+        }
+        let event = match &mut self.scanner {
+            Scanner::Set1(keyboard) => keyboard.add_byte(byte),
+            Scanner::Set2(keyboard) => keyboard.add_byte(byte),
+        };
+        let event = event.ok()??;
+        Some(self.process(event.code, event.state == KeyState::Down))
+    }
+
+    fn process(&mut self, code: KeyCode, pressed: bool) -> KeyEvent {
+        let modifiers = &mut self.modifiers;
+        match code {
+            KeyCode::ShiftLeft => modifiers.left_shift = pressed,
+            KeyCode::ShiftRight => modifiers.right_shift = pressed,
+            KeyCode::ControlLeft => modifiers.left_ctrl = pressed,
+            KeyCode::ControlRight => modifiers.right_ctrl = pressed,
+            KeyCode::AltLeft => modifiers.alt = pressed,
+            KeyCode::AltRight => modifiers.alt_gr = pressed,
+            KeyCode::CapsLock => {
+                if self.toggle_lock(CAPS_LOCK, pressed) {
+                    self.modifiers.caps_lock = !self.modifiers.caps_lock;
+                }
+            }
+            KeyCode::NumpadLock => {
+                if self.toggle_lock(NUM_LOCK, pressed) {
+                    self.modifiers.num_lock = !self.modifiers.num_lock;
+                }
+            }
+            KeyCode::ScrollLock => {
+                if self.toggle_lock(SCROLL_LOCK, pressed) {
+                    self.modifiers.scroll_lock = !self.modifiers.scroll_lock;
+                }
+            }
+            _ => {}
+        }
+
+        let ch = if pressed { self.translate(code) } else { None };
+        KeyEvent { code, pressed, modifiers: self.modifiers, ch }
+    }
+
+    // the character a key press types in the current layout and modifier state
+    fn translate(&mut self, code: KeyCode) -> Option<char> {
+        let modifiers = &self.modifiers;
+        let sym = if let Some(ch) = layout::numpad(code, modifiers).or_else(|| layout::special(code)) {
+            Sym::Char(ch)
+        } else {
+            let def = self.layout.key(code)?;
+            // control characters for Ctrl + letter, like a terminal
+            if modifiers.ctrl() && !layout::is_alt_gr(modifiers) {
+                return match def.normal {
+                    Some(Sym::Char(ch)) if ch.is_ascii_lowercase() => Some((ch as u8 - b'a' + 1) as char),
+                    _ => None,
+                };
+            }
+            def.sym(modifiers)?
+        };
+
+        match (sym, self.dead.take()) {
+            // the same dead key twice types the accent itself
+            (Sym::Dead(dead), Some(pending)) if dead == pending => Some(dead.spacing()),
+            (Sym::Dead(dead), _) => {
+                self.dead = Some(dead);
+                None
+            }
+            // a character that takes no accent drops it
+            (Sym::Char(ch), Some(pending)) => Some(pending.compose(ch).unwrap_or(ch)),
+            (Sym::Char(ch), None) => Some(ch),
+        }
+    }
+
+    // whether a lock key event toggles its lock: only the first press does
+    fn toggle_lock(&mut self, lock: u8, pressed: bool) -> bool {
+        let was_held = self.locks_held & lock != 0;
+        if pressed {
+            self.locks_held |= lock;
+        } else {
+            self.locks_held &= !lock;
+        }
+        pressed && !was_held
+    }
+}
+
+#[test_case]
+fn test_set1_shift_and_caps_lock() {
+    let mut decoder = Decoder::new(ScancodeSet::Set1);
+    assert_eq!(decoder.add_byte(0x1e).unwrap().ch, Some('a'));
+    assert_eq!(decoder.add_byte(0x9e).unwrap().ch, None); // release
+    decoder.add_byte(0x2a); // left shift
+    assert_eq!(decoder.add_byte(0x1e).unwrap().ch, Some('A'));
+    decoder.add_byte(0xaa);
+
+    // caps lock held down repeats, but only toggles once
+    decoder.add_byte(0x3a);
+    decoder.add_byte(0x3a);
+    decoder.add_byte(0xba);
+    assert!(decoder.modifiers().caps_lock);
+    assert_eq!(decoder.add_byte(0x1e).unwrap().ch, Some('A'));
+}
+
+#[test_case]
+fn test_set2_release_and_ack() {
+    let mut decoder = Decoder::new(ScancodeSet::Set2);
+    assert_eq!(decoder.add_byte(ACK), None);
+    let event = decoder.add_byte(0x1c).unwrap();
+    assert_eq!((event.code, event.pressed, event.ch), (KeyCode::A, true, Some('a')));
+    assert_eq!(decoder.add_byte(0xf0), None); // release prefix
+    let event = decoder.add_byte(0x1c).unwrap();
+    assert!(!event.pressed);
+}
+
+#[test_case]
+fn test_layouts_move_keys() {
+    let mut decoder = Decoder::new(ScancodeSet::Set1);
+    decoder.set_layout(Layout::Fr);
+    assert_eq!(decoder.add_byte(0x10).unwrap().ch, Some('a')); // Q key
+    assert_eq!(decoder.add_byte(0x02).unwrap().ch, Some('&')); // 1 key
+    decoder.set_layout(Layout::De);
+    assert_eq!(decoder.add_byte(0x15).unwrap().ch, Some('z')); // Y key
+    decoder.set_layout(Layout::Dvorak);
+    assert_eq!(decoder.add_byte(0x1f).unwrap().ch, Some('o')); // S key
+}
+
+#[test_case]
+fn test_alt_gr_and_dead_keys() {
+    let mut decoder = Decoder::new(ScancodeSet::Set1);
+    decoder.set_layout(Layout::De);
+    // AltGr is the right Alt key, E0 38
+    decoder.add_byte(0xe0);
+    decoder.add_byte(0x38);
+    assert_eq!(decoder.add_byte(0x10).unwrap().ch, Some('@')); // Q key
+    decoder.add_byte(0xe0);
+    decoder.add_byte(0xb8);
+
+    // acute accent, then e
+    assert_eq!(decoder.add_byte(0x0d).unwrap().ch, None);
+    assert_eq!(decoder.add_byte(0x12).unwrap().ch, Some('é'));
+    // circumflex, then space types the accent on its own
+    assert_eq!(decoder.add_byte(0x29).unwrap().ch, None);
+    assert_eq!(decoder.add_byte(0x39).unwrap().ch, Some('^'));
+    // a letter that takes no accent comes out plain
+    decoder.add_byte(0x29);
+    assert_eq!(decoder.add_byte(0x2e).unwrap().ch, Some('c'));
+}
+
+#[test_case]
+fn test_ctrl_letter_is_control_character() {
+    let mut decoder = Decoder::new(ScancodeSet::Set1);
+    decoder.add_byte(0x1d); // left control
+    let event = decoder.add_byte(0x16).unwrap(); // U
+    assert!(event.modifiers.ctrl());
+    assert_eq!(event.ch, Some('\u{15}'));
+}
@@ -0,0 +1,51 @@
+/* decoded key presses and the modifier state they happened in */
+
+pub use pc_keyboard::KeyCode;
+
+// modifier keys held, and lock keys active, when a key event happened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub alt: bool,
+    pub alt_gr: bool, // the right Alt key
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    // shift for letters, caps lock inverts it
+    pub fn upper_case(&self) -> bool {
+        self.shift() != self.caps_lock
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool, // false when the key was released
+    pub modifiers: Modifiers, // after this event was applied
+    pub ch: Option<char>, // what the key types, only set on presses
+}
+
+impl KeyEvent {
+    // shift, control, alt and the lock keys, which type nothing by themselves
+    pub fn is_modifier(&self) -> bool {
+        matches!(self.code,
+            KeyCode::ShiftLeft | KeyCode::ShiftRight
+            | KeyCode::ControlLeft | KeyCode::ControlRight
+            | KeyCode::AltLeft | KeyCode::AltRight
+            | KeyCode::CapsLock | KeyCode::NumpadLock | KeyCode::ScrollLock)
+    }
+}
@@ -0,0 +1,37 @@
+/* German QWERTZ layout */
+
+use super::{pair, triple, keydef, letter, letter3, us, DeadKey, KeyDef, Sym};
+use crate::task::keyboard::KeyCode;
+
+pub(super) fn key(code: KeyCode) -> Option<KeyDef> {
+    match code {
+        KeyCode::BackTick => keydef(Sym::Dead(DeadKey::Circumflex), Sym::Char('°'), None),
+        KeyCode::Key1 => pair('1', '!'),
+        KeyCode::Key2 => triple('2', '"', '²'),
+        KeyCode::Key3 => triple('3', '§', '³'),
+        KeyCode::Key4 => pair('4', '$'),
+        KeyCode::Key5 => pair('5', '%'),
+        KeyCode::Key6 => pair('6', '&'),
+        KeyCode::Key7 => triple('7', '/', '{'),
+        KeyCode::Key8 => triple('8', '(', '['),
+        KeyCode::Key9 => triple('9', ')', ']'),
+        KeyCode::Key0 => triple('0', '=', '}'),
+        KeyCode::Minus => triple('ß', '?', '\\'),
+        KeyCode::Equals => keydef(Sym::Dead(DeadKey::Acute), Sym::Dead(DeadKey::Grave), None),
+        KeyCode::Q => letter3('q', '@'),
+        KeyCode::E => letter3('e', '€'),
+        KeyCode::Y => letter('z'),
+        KeyCode::Z => letter('y'),
+        KeyCode::M => letter3('m', 'µ'),
+        KeyCode::BracketSquareLeft => letter('ü'),
+        KeyCode::BracketSquareRight => triple('+', '*', '~'),
+        KeyCode::SemiColon => letter('ö'),
+        KeyCode::Quote => letter('ä'),
+        KeyCode::BackSlash => pair('#', '\''),
+        KeyCode::HashTilde => triple('<', '>', '|'),
+        KeyCode::Comma => pair(',', ';'),
+        KeyCode::Fullstop => pair('.', ':'),
+        KeyCode::Slash => pair('-', '_'),
+        _ => us::letter_key(code),
+    }
+}
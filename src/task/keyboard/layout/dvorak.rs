@@ -0,0 +1,43 @@
+/* US Dvorak layout */
+
+use super::{pair, letter, us, KeyDef};
+use crate::task::keyboard::KeyCode;
+
+pub(super) fn key(code: KeyCode) -> Option<KeyDef> {
+    match code {
+        KeyCode::Minus => pair('[', '{'),
+        KeyCode::Equals => pair(']', '}'),
+        KeyCode::Q => pair('\'', '"'),
+        KeyCode::W => pair(',', '<'),
+        KeyCode::E => pair('.', '>'),
+        KeyCode::R => letter('p'),
+        KeyCode::T => letter('y'),
+        KeyCode::Y => letter('f'),
+        KeyCode::U => letter('g'),
+        KeyCode::I => letter('c'),
+        KeyCode::O => letter('r'),
+        KeyCode::P => letter('l'),
+        KeyCode::BracketSquareLeft => pair('/', '?'),
+        KeyCode::BracketSquareRight => pair('=', '+'),
+        KeyCode::S => letter('o'),
+        KeyCode::D => letter('e'),
+        KeyCode::F => letter('u'),
+        KeyCode::G => letter('i'),
+        KeyCode::H => letter('d'),
+        KeyCode::J => letter('h'),
+        KeyCode::K => letter('t'),
+        KeyCode::L => letter('n'),
+        KeyCode::SemiColon => letter('s'),
+        KeyCode::Quote => pair('-', '_'),
+        KeyCode::Z => pair(';', ':'),
+        KeyCode::X => letter('q'),
+        KeyCode::C => letter('j'),
+        KeyCode::V => letter('k'),
+        KeyCode::B => letter('x'),
+        KeyCode::N => letter('b'),
+        KeyCode::Comma => letter('w'),
+        KeyCode::Fullstop => letter('v'),
+        KeyCode::Slash => letter('z'),
+        _ => us::key(code), // A, M, the number row and backslash stay
+    }
+}
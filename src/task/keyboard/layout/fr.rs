@@ -0,0 +1,41 @@
+/* French AZERTY layout */
+
+use super::{pair, triple, keydef, letter, letter3, us, DeadKey, KeyDef, Sym};
+use crate::task::keyboard::KeyCode;
+
+// the digits are on the shift level of the number row
+pub(super) fn key(code: KeyCode) -> Option<KeyDef> {
+    match code {
+        KeyCode::BackTick => pair('²', '²'),
+        KeyCode::Key1 => pair('&', '1'),
+        KeyCode::Key2 => keydef(Sym::Char('é'), Sym::Char('2'), Some(Sym::Dead(DeadKey::Tilde))),
+        KeyCode::Key3 => triple('"', '3', '#'),
+        KeyCode::Key4 => triple('\'', '4', '{'),
+        KeyCode::Key5 => triple('(', '5', '['),
+        KeyCode::Key6 => triple('-', '6', '|'),
+        KeyCode::Key7 => keydef(Sym::Char('è'), Sym::Char('7'), Some(Sym::Dead(DeadKey::Grave))),
+        KeyCode::Key8 => triple('_', '8', '\\'),
+        KeyCode::Key9 => triple('ç', '9', '^'),
+        KeyCode::Key0 => triple('à', '0', '@'),
+        KeyCode::Minus => triple(')', '°', ']'),
+        KeyCode::Equals => triple('=', '+', '}'),
+        KeyCode::Q => letter('a'),
+        KeyCode::W => letter('z'),
+        KeyCode::E => letter3('e', '€'),
+        KeyCode::A => letter('q'),
+        KeyCode::SemiColon => letter('m'),
+        KeyCode::Z => letter('w'),
+        KeyCode::M => pair(',', '?'),
+        KeyCode::BracketSquareLeft => {
+            keydef(Sym::Dead(DeadKey::Circumflex), Sym::Dead(DeadKey::Diaeresis), None)
+        }
+        KeyCode::BracketSquareRight => triple('$', '£', '¤'),
+        KeyCode::Quote => pair('ù', '%'),
+        KeyCode::BackSlash => pair('*', 'µ'),
+        KeyCode::HashTilde => pair('<', '>'),
+        KeyCode::Comma => pair(';', '.'),
+        KeyCode::Fullstop => pair(':', '/'),
+        KeyCode::Slash => pair('!', '§'),
+        _ => us::letter_key(code),
+    }
+}
@@ -0,0 +1,228 @@
+/* keyboard layouts: what each key types, with AltGr and dead keys */
+
+mod us;
+mod uk;
+mod de;
+mod fr;
+mod dvorak;
+
+use super::event::{KeyCode, Modifiers};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Layout {
+    Us = 0,
+    Uk,
+    De, // QWERTZ
+    Fr, // AZERTY
+    Dvorak,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 5] = [Layout::Us, Layout::Uk, Layout::De, Layout::Fr, Layout::Dvorak];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+            Layout::Fr => "fr",
+            Layout::Dvorak => "dvorak",
+        }
+    }
+
+    // the layout called 'name', e.g. "de"
+    pub fn from_name(name: &str) -> Option<Layout> {
+        Layout::ALL.iter().copied().find(|layout| layout.name() == name)
+    }
+
+    // what the key types, None for keys that type nothing on every layout
+    pub(super) fn key(self, code: KeyCode) -> Option<KeyDef> {
+        match self {
+            Layout::Us => us::key(code),
+            Layout::Uk => uk::key(code),
+            Layout::De => de::key(code),
+            Layout::Fr => fr::key(code),
+            Layout::Dvorak => dvorak::key(code),
+        }
+    }
+}
+
+// layout used by the keyboard task
+static CURRENT: AtomicU8 = AtomicU8::new(Layout::Us as u8);
+
+pub fn current() -> Layout {
+    let index = CURRENT.load(Ordering::Relaxed);
+    Layout::ALL[usize::from(index)]
+}
+
+// takes effect with the next key
+pub fn set(layout: Layout) {
+    CURRENT.store(layout as u8, Ordering::Relaxed);
+}
+
+// an accent typed before the letter it goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadKey {
+    Grave,
+    Acute,
+    Circumflex,
+    Diaeresis,
+    Tilde,
+}
+
+impl DeadKey {
+    // the accent on its own, typed by the dead key followed by space
+    pub fn spacing(self) -> char {
+        match self {
+            DeadKey::Grave => '`',
+            DeadKey::Acute => '´',
+            DeadKey::Circumflex => '^',
+            DeadKey::Diaeresis => '¨',
+            DeadKey::Tilde => '~',
+        }
+    }
+
+    // the accented letter, None if 'ch' takes no such accent
+    pub fn compose(self, ch: char) -> Option<char> {
+        if ch == ' ' {
+            return Some(self.spacing());
+        }
+        let upper = ch.is_uppercase();
+        let lower = ch.to_ascii_lowercase();
+        let composed = match (self, lower) {
+            (DeadKey::Grave, 'a') => 'à',
+            (DeadKey::Grave, 'e') => 'è',
+            (DeadKey::Grave, 'i') => 'ì',
+            (DeadKey::Grave, 'o') => 'ò',
+            (DeadKey::Grave, 'u') => 'ù',
+            (DeadKey::Acute, 'a') => 'á',
+            (DeadKey::Acute, 'e') => 'é',
+            (DeadKey::Acute, 'i') => 'í',
+            (DeadKey::Acute, 'o') => 'ó',
+            (DeadKey::Acute, 'u') => 'ú',
+            (DeadKey::Acute, 'y') => 'ý',
+            (DeadKey::Circumflex, 'a') => 'â',
+            (DeadKey::Circumflex, 'e') => 'ê',
+            (DeadKey::Circumflex, 'i') => 'î',
+            (DeadKey::Circumflex, 'o') => 'ô',
+            (DeadKey::Circumflex, 'u') => 'û',
+            (DeadKey::Diaeresis, 'a') => 'ä',
+            (DeadKey::Diaeresis, 'e') => 'ë',
+            (DeadKey::Diaeresis, 'i') => 'ï',
+            (DeadKey::Diaeresis, 'o') => 'ö',
+            (DeadKey::Diaeresis, 'u') => 'ü',
+            (DeadKey::Diaeresis, 'y') => 'ÿ',
+            (DeadKey::Tilde, 'a') => 'ã',
+            (DeadKey::Tilde, 'n') => 'ñ',
+            (DeadKey::Tilde, 'o') => 'õ',
+            _ => return None,
+        };
+        if upper {
+            composed.to_uppercase().next()
+        } else {
+            Some(composed)
+        }
+    }
+}
+
+// what one level of a key produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Sym {
+    Char(char),
+    Dead(DeadKey),
+}
+
+// a key in a layout: its levels, and whether caps lock shifts it
+#[derive(Debug, Clone, Copy)]
+pub(super) struct KeyDef {
+    pub(super) normal: Option<Sym>,
+    pub(super) shift: Option<Sym>,
+    pub(super) alt_gr: Option<Sym>,
+    pub(super) caps: bool,
+}
+
+impl KeyDef {
+    // AltGr (or Ctrl+Alt) selects the third level, caps lock inverts
+    // shift on letters
+    pub(super) fn sym(&self, modifiers: &Modifiers) -> Option<Sym> {
+        if is_alt_gr(modifiers) {
+            self.alt_gr
+        } else if (self.caps && modifiers.upper_case()) || (!self.caps && modifiers.shift()) {
+            self.shift
+        } else {
+            self.normal
+        }
+    }
+}
+
+pub(super) fn is_alt_gr(modifiers: &Modifiers) -> bool {
+    modifiers.alt_gr || (modifiers.ctrl() && modifiers.alt)
+}
+
+// table helpers for the layouts
+
+// a letter, caps lock gives its upper case
+fn letter(ch: char) -> Option<KeyDef> {
+    Some(KeyDef {
+        normal: Some(Sym::Char(ch)),
+        shift: ch.to_uppercase().next().map(Sym::Char),
+        alt_gr: None,
+        caps: true,
+    })
+}
+
+fn letter3(ch: char, alt_gr: char) -> Option<KeyDef> {
+    letter(ch).map(|def| KeyDef { alt_gr: Some(Sym::Char(alt_gr)), ..def })
+}
+
+fn pair(normal: char, shift: char) -> Option<KeyDef> {
+    keydef(Sym::Char(normal), Sym::Char(shift), None)
+}
+
+fn triple(normal: char, shift: char, alt_gr: char) -> Option<KeyDef> {
+    keydef(Sym::Char(normal), Sym::Char(shift), Some(Sym::Char(alt_gr)))
+}
+
+fn keydef(normal: Sym, shift: Sym, alt_gr: Option<Sym>) -> Option<KeyDef> {
+    Some(KeyDef { normal: Some(normal), shift: Some(shift), alt_gr, caps: false })
+}
+
+// keys that type the same on every layout
+pub(super) fn special(code: KeyCode) -> Option<char> {
+    match code {
+        KeyCode::Spacebar => Some(' '),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Enter | KeyCode::NumpadEnter => Some('\n'),
+        KeyCode::Backspace => Some('\u{8}'),
+        KeyCode::Escape => Some('\u{1b}'),
+        KeyCode::Delete => Some('\u{7f}'),
+        KeyCode::NumpadSlash => Some('/'),
+        KeyCode::NumpadStar => Some('*'),
+        KeyCode::NumpadMinus => Some('-'),
+        KeyCode::NumpadPlus => Some('+'),
+        _ => None,
+    }
+}
+
+// the number pad types digits while num lock is on
+pub(super) fn numpad(code: KeyCode, modifiers: &Modifiers) -> Option<char> {
+    if !modifiers.num_lock {
+        return None;
+    }
+    match code {
+        KeyCode::Numpad0 => Some('0'),
+        KeyCode::Numpad1 => Some('1'),
+        KeyCode::Numpad2 => Some('2'),
+        KeyCode::Numpad3 => Some('3'),
+        KeyCode::Numpad4 => Some('4'),
+        KeyCode::Numpad5 => Some('5'),
+        KeyCode::Numpad6 => Some('6'),
+        KeyCode::Numpad7 => Some('7'),
+        KeyCode::Numpad8 => Some('8'),
+        KeyCode::Numpad9 => Some('9'),
+        KeyCode::NumpadPeriod => Some('.'),
+        _ => None,
+    }
+}
@@ -0,0 +1,23 @@
+/* UK 105 key layout */
+
+use super::{pair, triple, letter3, us, KeyDef};
+use crate::task::keyboard::KeyCode;
+
+// HashTilde is the extra ISO key left of Z
+pub(super) fn key(code: KeyCode) -> Option<KeyDef> {
+    match code {
+        KeyCode::BackTick => triple('`', '¬', '¦'),
+        KeyCode::Key2 => pair('2', '"'),
+        KeyCode::Key3 => pair('3', '£'),
+        KeyCode::Key4 => triple('4', '$', '€'),
+        KeyCode::Quote => pair('\'', '@'),
+        KeyCode::BackSlash => pair('#', '~'),
+        KeyCode::HashTilde => pair('\\', '|'),
+        KeyCode::A => letter3('a', 'á'),
+        KeyCode::E => letter3('e', 'é'),
+        KeyCode::I => letter3('i', 'í'),
+        KeyCode::O => letter3('o', 'ó'),
+        KeyCode::U => letter3('u', 'ú'),
+        _ => us::key(code),
+    }
+}
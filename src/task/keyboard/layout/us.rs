@@ -0,0 +1,49 @@
+/* US 104 key layout */
+
+use super::{pair, letter, KeyDef};
+use crate::task::keyboard::KeyCode;
+
+pub(super) fn key(code: KeyCode) -> Option<KeyDef> {
+    if let Some(def) = letter_key(code) {
+        return Some(def);
+    }
+    match code {
+        KeyCode::BackTick => pair('`', '~'),
+        KeyCode::Key1 => pair('1', '!'),
+        KeyCode::Key2 => pair('2', '@'),
+        KeyCode::Key3 => pair('3', '#'),
+        KeyCode::Key4 => pair('4', '$'),
+        KeyCode::Key5 => pair('5', '%'),
+        KeyCode::Key6 => pair('6', '^'),
+        KeyCode::Key7 => pair('7', '&'),
+        KeyCode::Key8 => pair('8', '*'),
+        KeyCode::Key9 => pair('9', '('),
+        KeyCode::Key0 => pair('0', ')'),
+        KeyCode::Minus => pair('-', '_'),
+        KeyCode::Equals => pair('=', '+'),
+        KeyCode::BracketSquareLeft => pair('[', '{'),
+        KeyCode::BracketSquareRight => pair(']', '}'),
+        KeyCode::BackSlash => pair('\\', '|'),
+        KeyCode::SemiColon => pair(';', ':'),
+        KeyCode::Quote => pair('\'', '"'),
+        KeyCode::Comma => pair(',', '<'),
+        KeyCode::Fullstop => pair('.', '>'),
+        KeyCode::Slash => pair('/', '?'),
+        _ => None,
+    }
+}
+
+// the letter keys, which most layouts share
+pub(super) fn letter_key(code: KeyCode) -> Option<KeyDef> {
+    let ch = match code {
+        KeyCode::A => 'a', KeyCode::B => 'b', KeyCode::C => 'c', KeyCode::D => 'd',
+        KeyCode::E => 'e', KeyCode::F => 'f', KeyCode::G => 'g', KeyCode::H => 'h',
+        KeyCode::I => 'i', KeyCode::J => 'j', KeyCode::K => 'k', KeyCode::L => 'l',
+        KeyCode::M => 'm', KeyCode::N => 'n', KeyCode::O => 'o', KeyCode::P => 'p',
+        KeyCode::Q => 'q', KeyCode::R => 'r', KeyCode::S => 's', KeyCode::T => 't',
+        KeyCode::U => 'u', KeyCode::V => 'v', KeyCode::W => 'w', KeyCode::X => 'x',
+        KeyCode::Y => 'y', KeyCode::Z => 'z',
+        _ => return None,
+    };
+    letter(ch)
+}
@@ -0,0 +1,95 @@
+/* the Caps/Num/Scroll Lock LEDs, set through the PS/2 controller */
+
+use super::event::Modifiers;
+use crate::time;
+use core::sync::atomic::{AtomicU8, Ordering};
+use x86_64::instructions::port::Port;
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const STATUS_INPUT_FULL: u8 = 0x02; // the controller has not taken the last byte yet
+
+const CMD_SET_LEDS: u8 = 0xed;
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+// the keyboard's answers to every byte sent to it
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const NO_RESPONSE: u8 = 0;
+
+// give up on a controller that never takes our byte
+const WRITE_TIMEOUT: u32 = 100_000;
+// and on a keyboard that doesn't answer
+const RESPONSE_TIMEOUT_MS: u64 = 20;
+const MAX_RESENDS: usize = 3;
+
+// the last ACK or RESEND, set by the keyboard interrupt handler
+static RESPONSE: AtomicU8 = AtomicU8::new(NO_RESPONSE);
+
+// Called by the keyboard interrupt handler with every byte it reads
+pub(super) fn note_response(byte: u8) {
+    if byte == ACK || byte == RESEND {
+        RESPONSE.store(byte, Ordering::Release);
+    }
+}
+
+fn write(byte: u8) {
+    let mut status: Port<u8> = Port::new(STATUS_PORT);
+    let mut data: Port<u8> = Port::new(DATA_PORT);
+    unsafe {
+        for _ in 0..WRITE_TIMEOUT {
+            if status.read() & STATUS_INPUT_FULL == 0 {
+                data.write(byte);
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// wait for the keyboard's answer to the byte just sent, it arrives through
+// the keyboard interrupt, which must be enabled
+fn response() -> Option<u8> {
+    let deadline = time::monotonic_nanos() + RESPONSE_TIMEOUT_MS * 1_000_000;
+    while time::monotonic_nanos() < deadline {
+        match RESPONSE.swap(NO_RESPONSE, Ordering::Acquire) {
+            NO_RESPONSE => core::hint::spin_loop(),
+            byte => return Some(byte),
+        }
+    }
+    None
+}
+
+// send one byte and wait for the ACK, the keyboard drops or misreads a byte
+// that comes before the last one was acknowledged; returns whether it was
+fn send(byte: u8) -> bool {
+    for _ in 0..MAX_RESENDS {
+        RESPONSE.store(NO_RESPONSE, Ordering::Release);
+        write(byte);
+        match response() {
+            Some(ACK) => return true,
+            Some(_) => continue, // RESEND
+            None => return false,
+        }
+    }
+    false
+}
+
+// the keyboard's ACKs also arrive as scancodes, the Decoder filters them out
+pub(super) fn set(modifiers: &Modifiers) {
+    let mut leds = 0;
+    if modifiers.scroll_lock {
+        leds |= LED_SCROLL_LOCK;
+    }
+    if modifiers.num_lock {
+        leds |= LED_NUM_LOCK;
+    }
+    if modifiers.caps_lock {
+        leds |= LED_CAPS_LOCK;
+    }
+    if send(CMD_SET_LEDS) {
+        send(leds);
+    }
+}
@@ -0,0 +1,186 @@
+/* the keyboard: raw scancodes from the interrupt handler, decoded into key events */
+
+mod decoder;
+mod event;
+mod layout;
+mod leds;
+mod polling;
+mod serial;
+
+pub use decoder::{Decoder, ScancodeSet};
+pub use event::{KeyCode, KeyEvent, Modifiers};
+pub use layout::{DeadKey, Layout};
+pub use polling::scroll_after_panic;
+pub use serial::{run_serial, SerialDecoder};
+pub(crate) use serial::add_byte as add_serial_byte;
+
+// the layout the keyboard task decodes with, switchable at any time
+pub fn layout() -> Layout {
+    layout::current()
+}
+
+pub fn set_layout(layout: Layout) {
+    layout::set(layout);
+}
+
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use core::{pin::Pin, task::{Poll, Context}};
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+
+use crate::println;
+use crate::vga_buffer::{console, BUFFER_HEIGHT};
+use x86_64::instructions::interrupts;
+use crate::thread::WaitQueue;
+use super::channel::broadcast;
+
+static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+static WAKER: AtomicWaker = AtomicWaker::new();
+static STREAM_TAKEN: AtomicBool = AtomicBool::new(false);
+// kernel threads blocked in read_scancode
+static THREADS: WaitQueue = WaitQueue::new();
+
+// decoded key events, a subscriber that falls further behind misses some
+const EVENT_CAPACITY: usize = 64;
+static EVENTS: OnceCell<broadcast::Sender<KeyEvent>> = OnceCell::uninit();
+
+// Called by the keyboard interrupt handler
+// must not block or allocate
+pub(crate) fn add_scancode(scancode: u8) {
+    leds::note_response(scancode);
+    if let Ok(queue) = SCANCODE_QUEUE.try_get() {
+        if let Err(_) = queue.push(scancode) {
+            println!("WARNING: scancode queue full; dropping keyboard input");
+        } else {
+            WAKER.wake(); // wake the task polling ScancodeStream
+            THREADS.notify_all();
+        }
+    } else {
+        println!("WARNING: scancode queue uninitialized");
+    }
+}
+
+// block the calling kernel thread until a scancode arrives
+// shares the queue with ScancodeStream, each scancode goes to one reader
+pub fn read_scancode() -> u8 {
+    let _ = SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100));
+    let queue = SCANCODE_QUEUE.try_get().expect("not initialized");
+    let mut scancode = None;
+    THREADS.wait_until(|| {
+        scancode = queue.pop();
+        scancode.is_some()
+    });
+    scancode.unwrap()
+}
+
+pub struct ScancodeStream {
+    _private: (),
+}
+
+impl ScancodeStream {
+    pub fn new() -> Self {
+        if STREAM_TAKEN.swap(true, Ordering::Acquire) {
+            panic!("only one ScancodeStream may exist at a time");
+        }
+        // the queue outlives the stream, a later stream reuses it
+        let _ = SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100));
+        ScancodeStream { _private: () } // prevent construction of the struct from outside of the module
+    }
+}
+
+impl Drop for ScancodeStream {
+    // release the stream so that another task can read the keyboard
+    fn drop(&mut self) {
+        WAKER.take();
+        STREAM_TAKEN.store(false, Ordering::Release);
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        if super::budget::consume(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let queue = SCANCODE_QUEUE
+            .try_get()
+            .expect("not initialized");
+
+        if let Some(scancode) = queue.pop() {
+            return Poll::Ready(Some(scancode));
+        }
+
+        WAKER.register(&cx.waker());
+        match queue.pop() {
+            // succeed: return the scancode wrapped
+            Some(scancode) => {
+                WAKER.take();
+                Poll::Ready(Some(scancode))
+            }
+            // fail: the queue is empty, wait
+            None => Poll::Pending,
+        }
+    }
+}
+
+fn events() -> &'static broadcast::Sender<KeyEvent> {
+    let _ = EVENTS.try_init_once(|| broadcast::channel(EVENT_CAPACITY).0);
+    EVENTS.try_get().expect("not initialized")
+}
+
+// receive every key event decoded from now on
+pub fn subscribe() -> broadcast::Receiver<KeyEvent> {
+    events().subscribe()
+}
+
+// Shift+PageUp/PageDown page through the console's scrollback and are
+// not published, any other key returns to the live output of the active console
+fn scroll_console(event: &KeyEvent) -> bool {
+    if !event.pressed || event.is_modifier() {
+        return false;
+    }
+    let page = BUFFER_HEIGHT / 2;
+    interrupts::without_interrupts(|| {
+        let mut writer = console::active_console().lock();
+        match event.code {
+            KeyCode::PageUp if event.modifiers.shift() => writer.scroll_up(page),
+            KeyCode::PageDown if event.modifiers.shift() => writer.scroll_down(page),
+            _ => {
+                writer.snap_to_live();
+                return false;
+            }
+        }
+        true
+    })
+}
+
+// the keyboard task: decodes the scancodes, keeps the LEDs in sync with
+// the lock keys and publishes the key events to the subscribers
+pub async fn run(set: ScancodeSet) {
+    let mut scancodes = ScancodeStream::new();
+    let mut decoder = Decoder::new(set);
+    let events = events();
+
+    while let Some(scancode) = scancodes.next().await {
+        let layout = layout::current();
+        if decoder.layout() != layout {
+            decoder.set_layout(layout);
+        }
+        let before = decoder.modifiers();
+        if let Some(event) = decoder.add_byte(scancode) {
+            let after = event.modifiers;
+            if (before.caps_lock, before.num_lock, before.scroll_lock)
+                != (after.caps_lock, after.num_lock, after.scroll_lock)
+            {
+                leds::set(&after);
+            }
+            if scroll_console(&event) {
+                continue;
+            }
+            events.send(event);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+/* reading the keyboard without interrupts, for when the tasks no longer run */
+
+use crate::vga_buffer::{console, BUFFER_HEIGHT};
+use x86_64::instructions::{interrupts, port::Port};
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const STATUS_OUTPUT_FULL: u8 = 0x01; // a byte is waiting in the data port
+const STATUS_FROM_MOUSE: u8 = 0x20;
+
+// scancode set 1, releases have bit 7 set
+const EXTENDED: u8 = 0xe0;
+const LEFT_SHIFT: u8 = 0x2a;
+const RIGHT_SHIFT: u8 = 0x36;
+const PAGE_UP: u8 = 0x49; // after EXTENDED
+const PAGE_DOWN: u8 = 0x51; // after EXTENDED
+const RELEASED: u8 = 0x80;
+
+// the next byte from the keyboard, None if there is none
+fn poll() -> Option<u8> {
+    unsafe {
+        let status = Port::<u8>::new(STATUS_PORT).read();
+        if status & STATUS_OUTPUT_FULL == 0 || status & STATUS_FROM_MOUSE != 0 {
+            return None;
+        }
+        Some(Port::<u8>::new(DATA_PORT).read())
+    }
+}
+
+// Called by the panic handler once the message is printed: the keyboard
+// task never runs again, so Shift+PageUp/PageDown are read here to page
+// through the scrollback of the active console
+pub fn scroll_after_panic() -> ! {
+    // the interrupt handler would take the scancodes first
+    interrupts::disable();
+    let page = BUFFER_HEIGHT / 2;
+    let (mut left_shift, mut right_shift, mut extended) = (false, false, false);
+    loop {
+        crate::watchdog::pet();
+        let scancode = match poll() {
+            Some(scancode) => scancode,
+            None => {
+                core::hint::spin_loop();
+                continue;
+            }
+        };
+        if scancode == EXTENDED {
+            extended = true;
+            continue;
+        }
+        let pressed = scancode & RELEASED == 0;
+        match (extended, scancode & !RELEASED) {
+            (false, LEFT_SHIFT) => left_shift = pressed,
+            (false, RIGHT_SHIFT) => right_shift = pressed,
+            (true, code @ (PAGE_UP | PAGE_DOWN)) if pressed && (left_shift || right_shift) => {
+                // the panic may have happened with the console locked
+                if let Some(mut writer) = console::active_console().try_lock() {
+                    if code == PAGE_UP {
+                        writer.scroll_up(page);
+                    } else {
+                        writer.scroll_down(page);
+                    }
+                }
+            }
+            _ => {}
+        }
+        extended = false;
+    }
+}
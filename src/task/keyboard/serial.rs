@@ -0,0 +1,204 @@
+/* keyboard input from a terminal on the serial port, turned into key events */
+
+use super::event::{KeyCode, KeyEvent, Modifiers};
+use conquer_once::spin::OnceCell;
+use core::{pin::Pin, task::{Context, Poll}};
+use crossbeam_queue::ArrayQueue;
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+
+static BYTE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// Called by the serial interrupt handler
+// must not block or allocate
+pub(crate) fn add_byte(byte: u8) {
+    // nobody reads the serial port before run_serial started
+    if let Ok(queue) = BYTE_QUEUE.try_get() {
+        if queue.push(byte).is_ok() {
+            WAKER.wake();
+        }
+    }
+}
+
+struct ByteStream;
+
+impl Stream for ByteStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        if crate::task::budget::consume(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let queue = BYTE_QUEUE.try_get().expect("not initialized");
+        if let Some(byte) = queue.pop() {
+            return Poll::Ready(Some(byte));
+        }
+        WAKER.register(cx.waker());
+        match queue.pop() {
+            Some(byte) => {
+                WAKER.take();
+                Poll::Ready(Some(byte))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+enum State {
+    Normal,
+    Escape, // after ESC
+    Csi(u8), // after ESC [, with the numeric parameter so far
+}
+
+// the bytes a VT100 terminal sends for the keys the line editor uses
+pub struct SerialDecoder {
+    state: State,
+}
+
+impl SerialDecoder {
+    pub fn new() -> SerialDecoder {
+        SerialDecoder { state: State::Normal }
+    }
+
+    pub fn add_byte(&mut self, byte: u8) -> Option<KeyEvent> {
+        match self.state {
+            State::Normal => match byte {
+                0x1b => {
+                    self.state = State::Escape;
+                    None
+                }
+                b'\r' | b'\n' => Some(press(KeyCode::Enter, Some('\n'), false)),
+                0x08 | 0x7f => Some(press(KeyCode::Backspace, Some('\u{8}'), false)),
+                b'\t' => Some(press(KeyCode::Tab, Some('\t'), false)),
+                // Ctrl + letter
+                0x01..=0x1a => {
+                    let code = letter_code(byte - 1 + b'a')?;
+                    Some(press(code, Some(char::from(byte)), true))
+                }
+                b' '..=b'~' => Some(press(ascii_code(byte), Some(char::from(byte)), false)),
+                _ => None, // no UTF-8, only ASCII terminals
+            },
+            State::Escape => {
+                self.state = if byte == b'[' { State::Csi(0) } else { State::Normal };
+                None
+            }
+            State::Csi(param) => {
+                self.state = State::Normal;
+                let code = match byte {
+                    b'0'..=b'9' => {
+                        self.state = State::Csi(param.saturating_mul(10).saturating_add(byte - b'0'));
+                        return None;
+                    }
+                    b'A' => KeyCode::ArrowUp,
+                    b'B' => KeyCode::ArrowDown,
+                    b'C' => KeyCode::ArrowRight,
+                    b'D' => KeyCode::ArrowLeft,
+                    b'H' => KeyCode::Home,
+                    b'F' => KeyCode::End,
+                    b'~' => match param {
+                        1 | 7 => KeyCode::Home,
+                        2 => KeyCode::Insert,
+                        3 => KeyCode::Delete,
+                        4 | 8 => KeyCode::End,
+                        5 => KeyCode::PageUp,
+                        6 => KeyCode::PageDown,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                Some(press(code, None, false))
+            }
+        }
+    }
+}
+
+impl Default for SerialDecoder {
+    fn default() -> Self {
+        SerialDecoder::new()
+    }
+}
+
+fn press(code: KeyCode, ch: Option<char>, ctrl: bool) -> KeyEvent {
+    let modifiers = Modifiers { left_ctrl: ctrl, ..Modifiers::default() };
+    KeyEvent { code, pressed: true, modifiers, ch }
+}
+
+// the key typing 'byte' on a US keyboard, the character is what counts
+fn ascii_code(byte: u8) -> KeyCode {
+    if let Some(code) = letter_code(byte.to_ascii_lowercase()) {
+        return code;
+    }
+    match byte {
+        b'1' | b'!' => KeyCode::Key1,
+        b'2' | b'@' => KeyCode::Key2,
+        b'3' | b'#' => KeyCode::Key3,
+        b'4' | b'$' => KeyCode::Key4,
+        b'5' | b'%' => KeyCode::Key5,
+        b'6' | b'^' => KeyCode::Key6,
+        b'7' | b'&' => KeyCode::Key7,
+        b'8' | b'*' => KeyCode::Key8,
+        b'9' | b'(' => KeyCode::Key9,
+        b'0' | b')' => KeyCode::Key0,
+        b'-' | b'_' => KeyCode::Minus,
+        b'=' | b'+' => KeyCode::Equals,
+        b'[' | b'{' => KeyCode::BracketSquareLeft,
+        b']' | b'}' => KeyCode::BracketSquareRight,
+        b'\\' | b'|' => KeyCode::BackSlash,
+        b';' | b':' => KeyCode::SemiColon,
+        b'\'' | b'"' => KeyCode::Quote,
+        b',' | b'<' => KeyCode::Comma,
+        b'.' | b'>' => KeyCode::Fullstop,
+        b'/' | b'?' => KeyCode::Slash,
+        b'`' | b'~' => KeyCode::BackTick,
+        _ => KeyCode::Spacebar,
+    }
+}
+
+fn letter_code(byte: u8) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F,
+        KeyCode::G, KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L,
+        KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+        KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+        KeyCode::Y, KeyCode::Z,
+    ];
+    match byte {
+        b'a'..=b'z' => Some(LETTERS[usize::from(byte - b'a')]),
+        _ => None,
+    }
+}
+
+// the serial input task: publishes what a terminal on COM1 types next to
+// the keyboard's events; the line editor draws the line being typed
+pub async fn run_serial() {
+    let _ = BYTE_QUEUE.try_init_once(|| ArrayQueue::new(100));
+    crate::serial::enable_receive_interrupt();
+    let mut bytes = ByteStream;
+    let mut decoder = SerialDecoder::new();
+    let events = super::events();
+
+    while let Some(byte) = bytes.next().await {
+        if let Some(event) = decoder.add_byte(byte) {
+            events.send(event);
+        }
+    }
+}
+
+#[test_case]
+fn test_serial_escape_sequences() {
+    let mut decoder = SerialDecoder::new();
+    let event = decoder.add_byte(b'x').unwrap();
+    assert_eq!((event.code, event.ch), (KeyCode::X, Some('x')));
+    assert!(decoder.add_byte(0x1b).is_none());
+    assert!(decoder.add_byte(b'[').is_none());
+    assert_eq!(decoder.add_byte(b'A').unwrap().code, KeyCode::ArrowUp);
+    for &byte in b"\x1b[3" {
+        assert!(decoder.add_byte(byte).is_none());
+    }
+    assert_eq!(decoder.add_byte(b'~').unwrap().code, KeyCode::Delete);
+    assert_eq!(decoder.add_byte(b'\r').unwrap().code, KeyCode::Enter);
+    let event = decoder.add_byte(0x17).unwrap(); // Ctrl+W
+    assert!(event.modifiers.ctrl());
+    assert_eq!(event.ch, Some('\u{17}'));
+}
@@ -0,0 +1,381 @@
+/* reading a line of text from the keyboard, with editing keys and a history */
+
+use super::channel::broadcast::RecvError;
+use super::keyboard::{self, KeyCode, KeyEvent};
+use crate::vga_buffer::{console::{self, CONSOLE_COUNT, SERIAL_CONSOLE}, BUFFER_WIDTH};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use core::mem;
+use super::sync;
+use lazy_static::lazy_static;
+use x86_64::instructions::interrupts;
+
+pub const DEFAULT_HISTORY_LEN: usize = 32;
+
+// control characters typed with Ctrl + letter
+const CTRL_U: char = '\u{15}'; // delete to the start of the line
+const CTRL_W: char = '\u{17}'; // delete the word before the cursor
+
+// the last submitted lines, oldest first
+pub struct History {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // empty lines and repeats of the last line are not recorded
+    pub fn push(&mut self, line: String) {
+        if self.capacity == 0 || line.is_empty() || self.entries.back() == Some(&line) {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(line);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // entry 'index' counted from the oldest
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+}
+
+// the line being edited; pure state, the keyboard and the screen are
+// only touched by read_line
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize, // index into 'line'
+    insert: bool, // false: typing overwrites
+    scroll: usize, // first character shown when the line is wider than the screen
+    history: History,
+    browsing: Option<usize>, // history entry shown by Up/Down
+    stash: Vec<char>, // the unsubmitted line while browsing the history
+}
+
+impl LineEditor {
+    pub fn new(history: History) -> LineEditor {
+        LineEditor {
+            line: Vec::new(),
+            cursor: 0,
+            insert: true,
+            scroll: 0,
+            history,
+            browsing: None,
+            stash: Vec::new(),
+        }
+    }
+
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_insert_mode(&self) -> bool {
+        self.insert
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn into_history(self) -> History {
+        self.history
+    }
+
+    // apply a key event, returns the line once Enter is pressed
+    pub fn handle(&mut self, event: &KeyEvent) -> Option<String> {
+        if !event.pressed {
+            return None;
+        }
+        match event.code {
+            KeyCode::Enter | KeyCode::NumpadEnter => return Some(self.submit()),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::ArrowLeft => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::ArrowRight => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Insert => self.insert = !self.insert,
+            KeyCode::ArrowUp => self.history_back(),
+            KeyCode::ArrowDown => self.history_forward(),
+            _ => match event.ch {
+                Some(CTRL_U) => self.delete_to_start(),
+                Some(CTRL_W) => self.delete_word(),
+                Some(ch) if !ch.is_control() => self.type_char(ch),
+                _ => {}
+            },
+        }
+        None
+    }
+
+    fn type_char(&mut self, ch: char) {
+        if self.insert || self.cursor == self.line.len() {
+            self.line.insert(self.cursor, ch);
+        } else {
+            self.line[self.cursor] = ch;
+        }
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.line.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+        }
+    }
+
+    fn delete_to_start(&mut self) {
+        self.line.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    // the spaces before the cursor, then the word before them
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.line[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.line[start - 1] != ' ' {
+            start -= 1;
+        }
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    fn history_back(&mut self) {
+        let index = match self.browsing {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.stash = mem::take(&mut self.line);
+                self.history.len() - 1
+            }
+        };
+        self.show_history(index);
+    }
+
+    fn history_forward(&mut self) {
+        match self.browsing {
+            None => {}
+            Some(index) if index + 1 < self.history.len() => self.show_history(index + 1),
+            Some(_) => {
+                // past the newest entry is the line we started with
+                self.browsing = None;
+                self.line = mem::take(&mut self.stash);
+                self.cursor = self.line.len();
+            }
+        }
+    }
+
+    fn show_history(&mut self, index: usize) {
+        self.browsing = Some(index);
+        self.line = self.history.get(index).unwrap_or("").chars().collect();
+        self.cursor = self.line.len();
+    }
+
+    fn submit(&mut self) -> String {
+        let line = self.line();
+        self.history.push(line.clone());
+        self.line.clear();
+        self.stash.clear();
+        self.cursor = 0;
+        self.scroll = 0;
+        self.browsing = None;
+        line
+    }
+
+    // the part of the line shown in 'width' columns, scrolled so that the
+    // cursor stays visible; returns the range of 'line' to show
+    pub fn view(&mut self, width: usize) -> (usize, usize) {
+        let width = width.max(1);
+        // the cursor may sit behind the last character
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + width {
+            self.scroll = self.cursor + 1 - width;
+        }
+        (self.scroll, self.line.len().min(self.scroll + width))
+    }
+
+    // draw the prompt and the visible part of the line on the current row of 'console'
+    fn render(&mut self, console: usize, prompt: &str) {
+        let prompt_len = prompt.chars().count();
+        let (start, end) = self.view(BUFFER_WIDTH.saturating_sub(prompt_len));
+        interrupts::without_interrupts(|| {
+            let mut writer = console::console(console).lock();
+            writer.set_column(0);
+            writer.write_string(prompt);
+            for &ch in &self.line[start..end] {
+                writer.write_char(ch);
+            }
+            writer.clear_to_end();
+            writer.set_column(prompt_len + self.cursor - start);
+        });
+        if console == SERIAL_CONSOLE {
+            // the terminal shows the same line: back to the first column,
+            // redraw, erase the rest and move the cursor into place
+            let line: String = self.line[start..end].iter().collect();
+            crate::serial_print!("\r{}{}\x1b[K\r", prompt, line);
+            let column = prompt_len + self.cursor - start;
+            if column > 0 {
+                crate::serial_print!("\x1b[{}C", column);
+            }
+        }
+    }
+
+    // show 'prompt' on 'console' and edit a line until Enter is pressed
+    // keys typed while another console is on the screen are meant for that one
+    pub async fn read_line(&mut self, console: usize, prompt: &str) -> String {
+        let mut keys = keyboard::subscribe();
+        self.render(console, prompt);
+        loop {
+            match keys.recv().await {
+                Ok(_) if console::active() != console => continue,
+                // redrawing would end scrolling back through the console
+                Ok(event) if !event.pressed || event.is_modifier() => continue,
+                Ok(event) => {
+                    if let Some(line) = self.handle(&event) {
+                        console::print_to(console, format_args!("\n"));
+                        if console == SERIAL_CONSOLE {
+                            crate::serial_print!("\r\n");
+                        }
+                        return line;
+                    }
+                    self.render(console, prompt);
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return self.submit(),
+            }
+        }
+    }
+}
+
+lazy_static! {
+    // one per console, the read_line caller on it holds it while reading
+    static ref HISTORIES: Vec<sync::Mutex<History>> = (0..CONSOLE_COUNT)
+        .map(|_| sync::Mutex::new(History::new(DEFAULT_HISTORY_LEN)))
+        .collect();
+}
+
+// lends the history to an editor and puts it back when the read finishes
+// or its future is dropped
+struct Borrowed<'a> {
+    slot: sync::MutexGuard<'a, History>,
+    editor: LineEditor,
+}
+
+impl Drop for Borrowed<'_> {
+    fn drop(&mut self) {
+        mem::swap(&mut *self.slot, &mut self.editor.history);
+    }
+}
+
+// read a line on 'console' from the keyboard with the console's history
+// a second reader on the same console waits until the first one is done
+pub async fn read_line(console: usize, prompt: &str) -> String {
+    let mut slot = HISTORIES[console].lock().await;
+    let history = mem::replace(&mut *slot, History::new(0));
+    let mut borrowed = Borrowed { slot, editor: LineEditor::new(history) };
+    borrowed.editor.read_line(console, prompt).await
+}
+
+#[cfg(test)]
+fn press(code: KeyCode, ch: Option<char>) -> KeyEvent {
+    KeyEvent { code, pressed: true, modifiers: Default::default(), ch }
+}
+
+#[cfg(test)]
+fn type_str(editor: &mut LineEditor, s: &str) {
+    for ch in s.chars() {
+        // the key code does not matter for printable characters
+        editor.handle(&press(KeyCode::A, Some(ch)));
+    }
+}
+
+#[test_case]
+fn test_editing_keys() {
+    let mut editor = LineEditor::new(History::new(4));
+    type_str(&mut editor, "helo");
+    editor.handle(&press(KeyCode::ArrowLeft, None));
+    type_str(&mut editor, "l");
+    assert_eq!(editor.line(), "hello");
+    editor.handle(&press(KeyCode::Home, None));
+    editor.handle(&press(KeyCode::Delete, Some('\u{7f}')));
+    editor.handle(&press(KeyCode::End, None));
+    editor.handle(&press(KeyCode::Backspace, Some('\u{8}')));
+    assert_eq!((editor.line().as_str(), editor.cursor()), ("ell", 3));
+
+    // overwrite mode
+    editor.handle(&press(KeyCode::Insert, None));
+    editor.handle(&press(KeyCode::Home, None));
+    type_str(&mut editor, "ab");
+    assert_eq!(editor.line(), "abl");
+}
+
+#[test_case]
+fn test_ctrl_w_and_ctrl_u() {
+    let mut editor = LineEditor::new(History::new(4));
+    type_str(&mut editor, "echo hello  world");
+    editor.handle(&press(KeyCode::W, Some(CTRL_W)));
+    assert_eq!(editor.line(), "echo hello  ");
+    editor.handle(&press(KeyCode::W, Some(CTRL_W)));
+    assert_eq!(editor.line(), "echo ");
+    type_str(&mut editor, "x");
+    editor.handle(&press(KeyCode::ArrowLeft, None));
+    editor.handle(&press(KeyCode::U, Some(CTRL_U)));
+    assert_eq!((editor.line().as_str(), editor.cursor()), ("x", 0));
+}
+
+#[test_case]
+fn test_history_navigation() {
+    let mut editor = LineEditor::new(History::new(2));
+    for line in ["one", "two", "three"] {
+        type_str(&mut editor, line);
+        assert_eq!(editor.handle(&press(KeyCode::Enter, Some('\n'))).as_deref(), Some(line));
+    }
+    // the ring only keeps the last two
+    assert_eq!(editor.history().len(), 2);
+
+    type_str(&mut editor, "draft");
+    editor.handle(&press(KeyCode::ArrowUp, None));
+    assert_eq!(editor.line(), "three");
+    editor.handle(&press(KeyCode::ArrowUp, None));
+    editor.handle(&press(KeyCode::ArrowUp, None));
+    assert_eq!(editor.line(), "two");
+    editor.handle(&press(KeyCode::ArrowDown, None));
+    editor.handle(&press(KeyCode::ArrowDown, None));
+    assert_eq!(editor.line(), "draft");
+}
+
+#[test_case]
+fn test_view_scrolls_with_cursor() {
+    let mut editor = LineEditor::new(History::new(0));
+    type_str(&mut editor, "0123456789");
+    assert_eq!(editor.view(4), (7, 10));
+    editor.handle(&press(KeyCode::Home, None));
+    assert_eq!(editor.view(4), (0, 4));
+}
@@ -1,21 +1,86 @@
 use core::{future::Future, pin::Pin};
 use core::task::{Context, Poll};
-use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::{boxed::Box, sync::Arc};
+use abort::AbortState;
 
 pub mod simple_executor;
+pub mod executor;
+pub mod work_stealing;
+pub mod join;
+pub mod abort;
+pub mod sync;
+pub mod channel;
+pub mod budget;
+pub mod stats;
 pub mod keyboard;
+pub mod line_editor;
+
+// unique identifier of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+pub use budget::yield_now;
+
+// scheduling class of a task, higher classes get more polls per round
+// but every ready class is served each round so none of them starves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High = 0,
+    Normal = 1,
+    Low = 2,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    // ready tasks of this class polled per scheduling round
+    pub fn weight(self) -> usize {
+        match self {
+            Priority::High => 8,
+            Priority::Normal => 4,
+            Priority::Low => 1,
+        }
+    }
+}
 
 pub struct Task {
+    id: TaskId,
+    priority: Priority,
     future: Pin<Box<dyn Future< Output = ()>>>,
+    abort: Arc<AbortState>,
 }
 
 impl Task {
     pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
         Task {
+            id: TaskId::new(),
+            priority: Priority::Normal,
             future: Box::pin(future),
+            abort: Arc::new(AbortState::new()),
         }
     }
 
+    pub fn with_priority(mut self, priority: Priority) -> Task {
+        self.priority = priority;
+        self
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
     // poll the stored furture
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
         self.future.as_mut().poll(context)
@@ -21,6 +21,10 @@ impl SimpleExecutor {
 
     pub fn run(&mut self) {
         while let Some(mut task) = self.task_queue.pop_front() {
+            crate::watchdog::pet();
+            if task.is_aborted() {
+                continue; // drop the cancelled task
+            }
             let waker = dummy_waker();
             let mut context = Context::from_waker(&waker);
             match task.poll(&mut context) {
@@ -0,0 +1,45 @@
+/* scheduling statistics of the tasks owned by an executor */
+
+use super::{Priority, TaskId};
+use alloc::{collections::BTreeMap, vec::Vec};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TaskStats {
+    pub priority: Priority,
+    pub polls: u64,
+    pub cycles: u64, // TSC cycles spent inside poll
+}
+
+static STATS: Mutex<BTreeMap<TaskId, TaskStats>> = Mutex::new(BTreeMap::new());
+
+// Called by the executor after polling a task
+pub(crate) fn record(task_id: TaskId, priority: Priority, cycles: u64) {
+    interrupts::without_interrupts(|| {
+        let mut stats = STATS.lock();
+        let entry = stats.entry(task_id).or_insert(TaskStats {
+            priority,
+            polls: 0,
+            cycles: 0,
+        });
+        entry.polls += 1;
+        entry.cycles += cycles;
+    });
+}
+
+// Called by the executor when a task is gone
+pub(crate) fn remove(task_id: TaskId) {
+    interrupts::without_interrupts(|| STATS.lock().remove(&task_id));
+}
+
+// statistics of all live tasks that were polled at least once
+pub fn snapshot() -> Vec<(TaskId, TaskStats)> {
+    interrupts::without_interrupts(|| {
+        STATS.lock().iter().map(|(id, stats)| (*id, *stats)).collect()
+    })
+}
+
+pub fn get(task_id: TaskId) -> Option<TaskStats> {
+    interrupts::without_interrupts(|| STATS.lock().get(&task_id).copied())
+}
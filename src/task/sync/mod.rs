@@ -0,0 +1,49 @@
+/* async synchronization primitives: waiting tasks register wakers instead of spinning */
+
+mod mutex;
+mod rwlock;
+mod semaphore;
+mod notify;
+
+pub use mutex::{Mutex, MutexGuard, MutexLockFuture};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard, RwLockReadFuture, RwLockWriteFuture};
+pub use semaphore::{Semaphore, SemaphorePermit, Acquire};
+pub use notify::{Notify, Notified};
+
+use alloc::vec::Vec;
+use core::mem;
+use core::task::Waker;
+use x86_64::instructions::interrupts;
+
+// wakers of the tasks waiting for a primitive
+// everybody is woken on release and re-checks the primitive when polled,
+// so a waiter whose future was dropped can't swallow the wakeup
+struct WaitList {
+    wakers: spin::Mutex<Vec<Waker>>,
+}
+
+impl WaitList {
+    const fn new() -> Self {
+        WaitList {
+            wakers: spin::Mutex::new(Vec::new()),
+        }
+    }
+
+    // registering and waking allocate and may drop the last reference to a
+    // task, so interrupt handlers must not use a WaitList
+    fn register(&self, waker: &Waker) {
+        interrupts::without_interrupts(|| {
+            let mut wakers = self.wakers.lock();
+            if !wakers.iter().any(|w| w.will_wake(waker)) {
+                wakers.push(waker.clone());
+            }
+        });
+    }
+
+    fn wake_all(&self) {
+        let wakers = interrupts::without_interrupts(|| mem::take(&mut *self.wakers.lock()));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
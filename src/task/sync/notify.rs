@@ -0,0 +1,72 @@
+use super::WaitList;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+// wake one or all waiting tasks; not from interrupt handlers, waking may
+// allocate and drop the woken task
+pub struct Notify {
+    // a notify_one nobody consumed yet, the next notified() returns at once
+    permit: AtomicBool,
+    // bumped by notify_waiters, releases every Notified created before
+    generation: AtomicUsize,
+    waiters: WaitList,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Notify {
+            permit: AtomicBool::new(false),
+            generation: AtomicUsize::new(0),
+            waiters: WaitList::new(),
+        }
+    }
+
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            generation: self.generation.load(Ordering::Acquire),
+        }
+    }
+
+    // release a single waiter, or the next one if nobody waits right now
+    pub fn notify_one(&self) {
+        self.permit.store(true, Ordering::Release);
+        self.waiters.wake_all();
+    }
+
+    // release every current waiter without storing a permit
+    pub fn notify_waiters(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        self.waiters.wake_all();
+    }
+}
+
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    generation: usize,
+}
+
+impl Notified<'_> {
+    fn try_complete(&self) -> bool {
+        self.notify.generation.load(Ordering::Acquire) != self.generation
+            || self.notify.permit.swap(false, Ordering::Acquire)
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.try_complete() {
+            return Poll::Ready(());
+        }
+        self.notify.waiters.register(cx.waker());
+        if self.try_complete() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::WaitList;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+// a pool of permits, acquiring waits asynchronously until enough are free
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: WaitList,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitList::new(),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Acquire)
+    }
+
+    pub fn add_permits(&self, count: usize) {
+        self.permits.fetch_add(count, Ordering::Release);
+        self.waiters.wake_all();
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    pub fn acquire_many(&self, count: usize) -> Acquire<'_> {
+        Acquire { semaphore: self, count }
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, count: usize) -> Option<SemaphorePermit<'_>> {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+        while permits >= count {
+            match self.permits.compare_exchange_weak(
+                permits, permits - count, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return Some(SemaphorePermit { semaphore: self, count }),
+                Err(current) => permits = current,
+            }
+        }
+        None
+    }
+}
+
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(permit) = self.semaphore.try_acquire_many(self.count) {
+            return Poll::Ready(permit);
+        }
+        self.semaphore.waiters.register(cx.waker());
+        match self.semaphore.try_acquire_many(self.count) {
+            Some(permit) => Poll::Ready(permit),
+            None => Poll::Pending,
+        }
+    }
+}
+
+// permits taken from a semaphore, given back when dropped
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+impl SemaphorePermit<'_> {
+    // keep the permits out of the semaphore for good
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            self.semaphore.add_permits(self.count);
+        }
+    }
+}
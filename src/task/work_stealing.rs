@@ -0,0 +1,283 @@
+/* multi-core executor: a run queue per CPU, idle CPUs steal, wakers route tasks back home */
+
+use super::{budget, stats, Priority, Task, TaskId};
+use super::join::JoinHandle;
+use crate::smp::{self, apic, MAX_CPUS};
+use crate::time::tsc;
+use alloc::{sync::Arc, task::Wake, vec::Vec};
+use core::future::Future;
+use core::sync::atomic::{self, AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use crossbeam_queue::ArrayQueue;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+// capacity of each run queue, wakers push into it from interrupt handlers
+const RUN_QUEUE_SIZE: usize = 256;
+
+// TaskCell::state
+const IDLE: u8 = 0; // waiting for a wake
+const QUEUED: u8 = 1; // in a run queue, a second wake is a no-op
+const RUNNING: u8 = 2; // being polled, a wake queues it after the poll
+const RUNNING_WOKEN: u8 = 3; // woken while being polled
+
+// a spawned task, shared by the run queues and its waker
+struct TaskCell {
+    id: TaskId,
+    priority: Priority,
+    // only the CPU that moved the state to RUNNING locks it, so nobody waits
+    task: Mutex<Option<Task>>, // None once it completed or was aborted
+    home: AtomicUsize, // CPU that polled it last, its waker queues it there
+    state: AtomicU8,
+}
+
+// a Task future need not be Send, but only Send futures are spawned here
+unsafe impl Send for TaskCell {}
+unsafe impl Sync for TaskCell {}
+
+// run queues of one CPU, one per priority
+struct Core {
+    queues: [ArrayQueue<Arc<TaskCell>>; Priority::ALL.len()],
+    sleeping: AtomicBool, // halted, needs an IPI to see new work
+}
+
+lazy_static! {
+    static ref CORES: Vec<Core> = (0..MAX_CPUS)
+        .map(|_| Core {
+            queues: Priority::ALL.map(|_| ArrayQueue::new(RUN_QUEUE_SIZE)),
+            sleeping: AtomicBool::new(false),
+        })
+        .collect();
+}
+
+// spawned tasks that have not completed yet
+static TASKS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    spawn_task(Task::new(future));
+}
+
+pub fn spawn_with_priority<F>(future: F, priority: Priority)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    spawn_task(Task::new(future).with_priority(priority));
+}
+
+// spawn a future and get a handle resolving to its output
+pub fn spawn_with_handle<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (task, handle) = Task::with_handle(future);
+    spawn_task(task);
+    handle
+}
+
+// the task was built from a Send future
+fn spawn_task(task: Task) {
+    let cpu = smp::current_cpu();
+    let cell = Arc::new(TaskCell {
+        id: task.id,
+        priority: task.priority,
+        task: Mutex::new(Some(task)),
+        home: AtomicUsize::new(cpu),
+        state: AtomicU8::new(IDLE),
+    });
+    TASKS.fetch_add(1, Ordering::Relaxed);
+    schedule(cell);
+    // let an idle CPU come and steal it
+    if let Some(idle) = (0..smp::cpu_count()).find(|&other| other != cpu && is_sleeping(other)) {
+        wake_cpu(idle);
+    }
+}
+
+// number of spawned tasks that have not completed yet
+pub fn task_count() -> usize {
+    TASKS.load(Ordering::Relaxed)
+}
+
+// queue a task on its home CPU, called by wakers, also from interrupt handlers
+fn schedule(cell: Arc<TaskCell>) {
+    let mut state = cell.state.load(Ordering::Acquire);
+    loop {
+        let next = match state {
+            IDLE => QUEUED,
+            RUNNING => RUNNING_WOKEN,
+            _ => return, // QUEUED or RUNNING_WOKEN, it will be polled
+        };
+        match cell.state.compare_exchange_weak(state, next, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) if next == QUEUED => break,
+            Ok(_) => return, // run_task queues it after the poll
+            Err(current) => state = current,
+        }
+    }
+    enqueue(cell);
+}
+
+// push a task in state QUEUED into a run queue
+fn enqueue(cell: Arc<TaskCell>) {
+    let home = cell.home.load(Ordering::Relaxed);
+    let priority = cell.priority as usize;
+    let mut cell = match CORES[home].queues[priority].push(cell) {
+        Ok(()) => {
+            wake_if_sleeping(home);
+            return;
+        }
+        Err(cell) => cell,
+    };
+    // the home queue is full, any other CPU can run it as well
+    for cpu in (0..smp::cpu_count()).filter(|&cpu| cpu != home) {
+        match CORES[cpu].queues[priority].push(cell) {
+            Ok(()) => {
+                wake_if_sleeping(cpu);
+                return;
+            }
+            Err(back) => cell = back,
+        }
+    }
+    panic!("all run queues full");
+}
+
+fn is_sleeping(cpu: usize) -> bool {
+    CORES[cpu].sleeping.load(Ordering::SeqCst)
+}
+
+fn wake_if_sleeping(cpu: usize) {
+    // pairs with the fence in sleep_if_idle: either the sleeper sees the
+    // queued task or we see it sleeping
+    atomic::fence(Ordering::SeqCst);
+    if cpu != smp::current_cpu() && is_sleeping(cpu) {
+        wake_cpu(cpu);
+    }
+}
+
+fn wake_cpu(cpu: usize) {
+    if let Some(apic_id) = smp::apic_id(cpu) {
+        apic::send_ipi(apic_id, apic::WAKEUP_VECTOR);
+    }
+}
+
+// own queue first, then the same priority of the other CPUs
+fn next_task(cpu: usize, priority: Priority) -> Option<Arc<TaskCell>> {
+    let priority = priority as usize;
+    if let Some(cell) = CORES[cpu].queues[priority].pop() {
+        return Some(cell);
+    }
+    let count = smp::cpu_count();
+    (1..count)
+        .map(|offset| (cpu + offset) % count)
+        .find_map(|victim| CORES[victim].queues[priority].pop())
+}
+
+fn has_work(cpu: usize) -> bool {
+    let count = smp::cpu_count();
+    (0..count)
+        .map(|offset| (cpu + offset) % count)
+        .any(|other| CORES[other].queues.iter().any(|queue| !queue.is_empty()))
+}
+
+// poll ready tasks in weighted rounds until no queue has any left
+fn run_ready_tasks(cpu: usize) {
+    loop {
+        let mut polled = false;
+        for priority in Priority::ALL {
+            for _ in 0..priority.weight() {
+                match next_task(cpu, priority) {
+                    Some(cell) => {
+                        run_task(cpu, cell);
+                        polled = true;
+                    }
+                    None => break,
+                }
+            }
+        }
+        if !polled {
+            break;
+        }
+    }
+}
+
+fn run_task(cpu: usize, cell: Arc<TaskCell>) {
+    crate::watchdog::pet();
+    // a stolen task now belongs to this CPU
+    cell.home.store(cpu, Ordering::Relaxed);
+    // wakes from here on are remembered and queue it after the poll
+    cell.state.store(RUNNING, Ordering::Release);
+
+    let mut slot = cell.task.lock();
+    let (finished, cycles) = match slot.as_mut() {
+        None => (false, None), // completed, woken again afterwards
+        Some(task) if task.is_aborted() => (true, None),
+        Some(task) => {
+            let waker = Waker::from(cell.clone());
+            task.register_waker(&waker);
+            let mut context = Context::from_waker(&waker);
+
+            budget::reset();
+            let start = tsc::read();
+            let result = task.poll(&mut context);
+            let cycles = tsc::read() - start;
+            budget::unconstrain();
+            (result.is_ready(), Some(cycles))
+        }
+    };
+    // drop the future outside the lock, dropping it may wake other tasks
+    let task = if finished { slot.take() } else { None };
+    drop(slot);
+    if let Some(cycles) = cycles {
+        stats::record(cell.id, cell.priority, cycles);
+    }
+    if finished {
+        drop(task);
+        stats::remove(cell.id);
+        TASKS.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    if cell.state.compare_exchange(RUNNING, IDLE, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        // woken during the poll
+        cell.state.store(QUEUED, Ordering::Release);
+        enqueue(cell);
+    }
+}
+
+// halt until the next interrupt if no CPU has a task ready
+fn sleep_if_idle(cpu: usize) {
+    // an IPI between the check and hlt would be slept through, so check with
+    // interrupts disabled and re-enable them atomically with hlt
+    interrupts::disable();
+    CORES[cpu].sleeping.store(true, Ordering::SeqCst);
+    atomic::fence(Ordering::SeqCst);
+    if has_work(cpu) {
+        interrupts::enable();
+    } else {
+        enable_and_hlt();
+    }
+    CORES[cpu].sleeping.store(false, Ordering::SeqCst);
+    // an idle CPU is not stuck, its local timer wakes it up regularly
+    crate::watchdog::pet();
+}
+
+// run the tasks of this CPU and steal from the others, every CPU calls this
+pub fn run() -> ! {
+    let cpu = smp::current_cpu();
+    loop {
+        run_ready_tasks(cpu);
+        sleep_if_idle(cpu);
+    }
+}
+
+impl Wake for TaskCell {
+    fn wake(self: Arc<Self>) {
+        schedule(self);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        schedule(self.clone());
+    }
+}
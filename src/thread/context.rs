@@ -0,0 +1,86 @@
+/* saving and restoring the registers of a kernel thread */
+
+use core::arch::global_asm;
+use core::mem::size_of;
+
+// thread_switch saves the callee-saved registers and RFLAGS on the old stack,
+// stores the old stack pointer through 'old_rsp' (rdi) and restores the same
+// registers from the stack at 'new_rsp' (rsi). Caller-saved registers are
+// already saved by the compiler around the call.
+global_asm!(
+    ".global thread_switch",
+    "thread_switch:",
+    "    pushfq",
+    "    push rbp",
+    "    push rbx",
+    "    push r12",
+    "    push r13",
+    "    push r14",
+    "    push r15",
+    "    mov [rdi], rsp",
+    "    mov rsp, rsi",
+    "    pop r15",
+    "    pop r14",
+    "    pop r13",
+    "    pop r12",
+    "    pop rbx",
+    "    pop rbp",
+    "    popfq",
+    "    ret",
+    "",
+    // first code of a new thread, its argument was placed in r12 by 'init_stack'
+    ".global thread_trampoline",
+    "thread_trampoline:",
+    "    mov rdi, r12",
+    "    and rsp, -16",
+    "    call {start}",
+    "    ud2",
+    start = sym super::thread_start,
+);
+
+extern "C" {
+    fn thread_switch(old_rsp: *mut u64, new_rsp: u64);
+    fn thread_trampoline();
+}
+
+// what thread_switch pops from a stack that never ran
+#[repr(C)]
+struct InitialFrame {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    rbx: u64,
+    rbp: u64,
+    rflags: u64,
+    return_address: u64,
+}
+
+// only the reserved bit, so the thread starts with interrupts disabled
+// like the scheduler that switches to it
+const INITIAL_RFLAGS: u64 = 0x2;
+
+// prepare a fresh stack so that switching to it enters thread_trampoline
+// with 'arg' in r12, returns the stack pointer to switch to
+pub(super) fn init_stack(stack_top: u64, arg: u64) -> u64 {
+    let frame = (stack_top & !0xf) - size_of::<InitialFrame>() as u64;
+    unsafe {
+        (frame as *mut InitialFrame).write(InitialFrame {
+            r15: 0,
+            r14: 0,
+            r13: 0,
+            r12: arg,
+            rbx: 0,
+            rbp: 0,
+            rflags: INITIAL_RFLAGS,
+            return_address: thread_trampoline as usize as u64,
+        });
+    }
+    frame
+}
+
+// save the current context into 'old_rsp' and continue with the one at 'new_rsp'
+// interrupts must be disabled and no lock may be held
+pub(super) unsafe fn switch(old_rsp: *mut u64, new_rsp: u64) {
+    thread_switch(old_rsp, new_rsp);
+}
@@ -0,0 +1,199 @@
+/* preemptive kernel threads: own stacks, saved contexts, round-robin on the timer tick */
+
+mod context;
+mod scheduler;
+mod stack;
+mod wait;
+
+pub use wait::WaitQueue;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use scheduler::{Scheduler, Thread, MAX_THREADS, SCHEDULER};
+
+pub const STACK_SIZE: usize = 4096 * 4; // 16 KiB, not counting the guard page
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Running,
+    Ready,
+    Blocked, // waiting on a WaitQueue
+    Sleeping, // waiting for a timer tick deadline
+    Exited,
+}
+
+// turn the code running now into the boot thread, the heap must be initialized
+pub fn init() {
+    let boot = ThreadId::new();
+    // the idle thread runs whenever all others are blocked or asleep
+    let idle = new_thread(Box::new(|| loop {
+        interrupts::enable_and_hlt();
+    }));
+    let scheduler = Scheduler::new(boot, idle);
+    interrupts::without_interrupts(|| {
+        let mut slot = SCHEDULER.lock();
+        assert!(slot.is_none(), "thread::init called twice");
+        *slot = Some(scheduler);
+    });
+}
+
+// the threads run on the BSP only, calling this, yield_now, sleep_ms or
+// blocking on a WaitQueue from another CPU panics
+pub fn current() -> ThreadId {
+    scheduler::assert_on_bsp();
+    interrupts::without_interrupts(|| {
+        SCHEDULER.lock().as_ref().expect("threads not initialized").current()
+    })
+}
+
+// None once the thread exited and was cleaned up
+pub fn state(id: ThreadId) -> Option<ThreadState> {
+    interrupts::without_interrupts(|| {
+        SCHEDULER.lock().as_ref().and_then(|scheduler| scheduler.state(id))
+    })
+}
+
+// start a thread running 'f' on its own stack
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        result: Mutex::new(None),
+        finished: WaitQueue::new(),
+    });
+    let shared_clone = shared.clone();
+    // allocate everything before taking the scheduler lock
+    let thread = new_thread(Box::new(move || {
+        let value = f();
+        // join checks the result with interrupts disabled, so it must never
+        // find the lock held by a preempted thread
+        interrupts::without_interrupts(|| *shared_clone.result.lock() = Some(value));
+        shared_clone.finished.notify_all();
+    }));
+    let id = thread.id;
+
+    let added = interrupts::without_interrupts(|| {
+        SCHEDULER.lock().as_mut().expect("threads not initialized").add(thread)
+    });
+    if added.is_err() {
+        panic!("more than {} threads", MAX_THREADS);
+    }
+    reap();
+    JoinHandle { id, shared }
+}
+
+fn new_thread(entry: Box<dyn FnOnce() + Send>) -> Box<Thread> {
+    let entry = Box::into_raw(Box::new(entry));
+    let stack = stack::Stack::new();
+    let rsp = context::init_stack(stack.top(), entry as u64);
+    Box::new(Thread::new(ThreadId::new(), stack, rsp))
+}
+
+// give the CPU to the next ready thread
+pub fn yield_now() {
+    interrupts::without_interrupts(|| scheduler::switch_away(ThreadState::Ready));
+}
+
+// block the current thread for at least 'ms' milliseconds
+// the wake up happens on a timer tick, so the resolution is 1 / TICK_HZ
+pub fn sleep_ms(ms: u64) {
+    let tick_hz = u64::from(crate::time::TICK_HZ);
+    let ticks = ((ms * tick_hz + 999) / 1000).max(1);
+    // the current tick is partly over, wait for one more
+    let wake_at = crate::time::ticks() + ticks + 1;
+    interrupts::without_interrupts(|| {
+        scheduler::switch_with(|scheduler| {
+            scheduler.sleep_current(wake_at);
+            scheduler.prepare_switch(ThreadState::Sleeping)
+        });
+    });
+}
+
+// end the current thread, its stack is freed by the next spawn or join
+pub fn exit() -> ! {
+    interrupts::disable();
+    scheduler::switch_away(ThreadState::Exited);
+    unreachable!("exited thread was scheduled again");
+}
+
+// Called by the timer interrupt handler after the end of interrupt was sent
+// Locks that threads share, the allocator's included (see Locked::lock), are
+// held with interrupts disabled, so a preempted thread never holds one
+pub(crate) fn preempt() {
+    let now = crate::time::ticks();
+    scheduler::switch_with(|scheduler| {
+        scheduler.wake_sleepers(now);
+        scheduler.prepare_switch(ThreadState::Ready)
+    });
+}
+
+// free the stacks of exited threads, must not run in an interrupt handler
+fn reap() {
+    let mut zombies = Vec::with_capacity(MAX_THREADS);
+    interrupts::without_interrupts(|| {
+        if let Some(scheduler) = SCHEDULER.lock().as_mut() {
+            scheduler.take_zombies(&mut zombies);
+        }
+    });
+    drop(zombies);
+}
+
+// first Rust code of every new thread, entered from thread_trampoline
+extern "C" fn thread_start(entry: *mut Box<dyn FnOnce() + Send>) -> ! {
+    // the scheduler switched here with interrupts disabled
+    interrupts::enable();
+    let entry = unsafe { Box::from_raw(entry) };
+    (*entry)();
+    exit();
+}
+
+// shared between a thread and its JoinHandle
+struct Shared<T> {
+    result: Mutex<Option<T>>,
+    finished: WaitQueue,
+}
+
+pub struct JoinHandle<T> {
+    id: ThreadId,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    pub fn is_finished(&self) -> bool {
+        interrupts::without_interrupts(|| self.shared.result.lock().is_some())
+    }
+
+    // block until the thread finished and return its result
+    // a thread that called exit() has no result, joining it blocks forever
+    pub fn join(self) -> T {
+        let mut value = None;
+        self.shared.finished.wait_until(|| {
+            value = self.shared.result.lock().take();
+            value.is_some()
+        });
+        reap();
+        value.unwrap()
+    }
+}
@@ -0,0 +1,213 @@
+/* round-robin scheduler: the thread table, the ready queue and switching between them */
+
+use super::context;
+use super::stack::Stack;
+use super::{ThreadId, ThreadState};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::mem;
+use spin::Mutex;
+
+// the tables are allocated up front: the scheduler runs in the timer
+// interrupt and must never allocate, the preempted thread could hold the
+// allocator lock
+pub(super) const MAX_THREADS: usize = 64;
+
+pub(super) struct Thread {
+    pub(super) id: ThreadId,
+    pub(super) state: ThreadState,
+    rsp: u64, // saved stack pointer while not running
+    _stack: Option<Stack>, // None for the boot thread
+    wait_key: usize, // the WaitQueue a blocked thread waits on
+    block_order: u64, // wake blocked threads first come, first served
+    wake_at: u64, // tick a sleeping thread waits for
+}
+
+impl Thread {
+    pub(super) fn new(id: ThreadId, stack: Stack, rsp: u64) -> Thread {
+        Thread {
+            id,
+            state: ThreadState::Ready,
+            rsp,
+            _stack: Some(stack),
+            wait_key: 0,
+            block_order: 0,
+            wake_at: 0,
+        }
+    }
+}
+
+pub(super) struct Scheduler {
+    threads: Vec<Box<Thread>>,
+    ready: VecDeque<ThreadId>,
+    current: ThreadId,
+    idle: ThreadId, // runs when every other thread is blocked, never queued
+    zombies: Vec<Box<Thread>>, // exited threads whose stacks can be freed
+    next_block_order: u64,
+}
+
+pub(super) static SCHEDULER: Mutex<Option<Scheduler>> = Mutex::new(None);
+
+impl Scheduler {
+    // the code running right now becomes the boot thread
+    pub(super) fn new(boot: ThreadId, idle: Box<Thread>) -> Scheduler {
+        let mut threads = Vec::with_capacity(MAX_THREADS);
+        threads.push(Box::new(Thread {
+            id: boot,
+            state: ThreadState::Running,
+            rsp: 0,
+            _stack: None,
+            wait_key: 0,
+            block_order: 0,
+            wake_at: 0,
+        }));
+        let idle_id = idle.id;
+        threads.push(idle);
+        Scheduler {
+            threads,
+            ready: VecDeque::with_capacity(MAX_THREADS),
+            current: boot,
+            idle: idle_id,
+            zombies: Vec::with_capacity(MAX_THREADS),
+            next_block_order: 0,
+        }
+    }
+
+    pub(super) fn current(&self) -> ThreadId {
+        self.current
+    }
+
+    fn index(&self, id: ThreadId) -> Option<usize> {
+        self.threads.iter().position(|t| t.id == id)
+    }
+
+    fn current_mut(&mut self) -> &mut Thread {
+        let index = self.index(self.current).expect("current thread missing");
+        &mut self.threads[index]
+    }
+
+    pub(super) fn state(&self, id: ThreadId) -> Option<ThreadState> {
+        self.index(id).map(|i| self.threads[i].state)
+    }
+
+    // add a ready thread, hands it back if the table is full
+    pub(super) fn add(&mut self, thread: Box<Thread>) -> Result<(), Box<Thread>> {
+        if self.threads.len() == MAX_THREADS {
+            return Err(thread);
+        }
+        self.ready.push_back(thread.id);
+        self.threads.push(thread);
+        Ok(())
+    }
+
+    // exchange the zombie list with an empty one of full capacity, so the
+    // caller can free the stacks after releasing the lock
+    pub(super) fn take_zombies(&mut self, empty: &mut Vec<Box<Thread>>) {
+        mem::swap(&mut self.zombies, empty);
+    }
+
+    fn make_ready(&mut self, index: usize) {
+        let thread = &mut self.threads[index];
+        thread.state = ThreadState::Ready;
+        thread.wait_key = 0;
+        self.ready.push_back(thread.id);
+    }
+
+    // wake the longest waiting thread (or all of them) blocked on 'wait_key'
+    pub(super) fn wake(&mut self, wait_key: usize, all: bool) -> usize {
+        let mut woken = 0;
+        loop {
+            let first = self.threads.iter()
+                .enumerate()
+                .filter(|(_, t)| t.state == ThreadState::Blocked && t.wait_key == wait_key)
+                .min_by_key(|(_, t)| t.block_order)
+                .map(|(i, _)| i);
+            match first {
+                Some(index) => self.make_ready(index),
+                None => break,
+            }
+            woken += 1;
+            if !all {
+                break;
+            }
+        }
+        woken
+    }
+
+    // make sleeping threads whose deadline passed ready again
+    pub(super) fn wake_sleepers(&mut self, now: u64) {
+        for index in 0..self.threads.len() {
+            let thread = &self.threads[index];
+            if thread.state == ThreadState::Sleeping && thread.wake_at <= now {
+                self.make_ready(index);
+            }
+        }
+    }
+
+    pub(super) fn block_current(&mut self, wait_key: usize) {
+        let order = self.next_block_order;
+        self.next_block_order += 1;
+        let current = self.current_mut();
+        current.wait_key = wait_key;
+        current.block_order = order;
+    }
+
+    pub(super) fn sleep_current(&mut self, wake_at: u64) {
+        self.current_mut().wake_at = wake_at;
+    }
+
+    // pick the next thread and put the current one into 'state'
+    // returns the stack pointers to switch with, None to keep running
+    pub(super) fn prepare_switch(&mut self, state: ThreadState) -> Option<(*mut u64, u64)> {
+        let next = match self.ready.pop_front() {
+            Some(next) => next,
+            None if state == ThreadState::Ready => return None,
+            None => self.idle, // everybody is waiting
+        };
+
+        let current = self.index(self.current).expect("current thread missing");
+        self.threads[current].state = state;
+        // the Box keeps the saved stack pointer at a stable address
+        let old_rsp: *mut u64 = &mut self.threads[current].rsp;
+        match state {
+            ThreadState::Ready if self.current != self.idle => self.ready.push_back(self.current),
+            ThreadState::Ready | ThreadState::Blocked | ThreadState::Sleeping => {}
+            ThreadState::Exited => {
+                let thread = self.threads.swap_remove(current);
+                self.zombies.push(thread);
+            }
+            ThreadState::Running => unreachable!(),
+        }
+
+        let next_index = self.index(next).expect("ready thread missing");
+        let next_thread = &mut self.threads[next_index];
+        next_thread.state = ThreadState::Running;
+        self.current = next;
+        Some((old_rsp, next_thread.rsp))
+    }
+}
+
+// run 'f' on the scheduler and switch threads if it asks to
+// must be called with interrupts disabled
+pub(super) fn switch_with(f: impl FnOnce(&mut Scheduler) -> Option<(*mut u64, u64)>) {
+    assert_on_bsp();
+    let switch = match SCHEDULER.lock().as_mut() {
+        Some(scheduler) => f(scheduler),
+        None => None, // threads are not initialized
+    };
+    // the lock is released before switching, the next thread may need it
+    if let Some((old_rsp, new_rsp)) = switch {
+        unsafe { context::switch(old_rsp, new_rsp) };
+    }
+}
+
+// the scheduler keeps one current thread, the BSP's; switching from
+// another CPU would save that CPU's context as the BSP thread's
+pub(super) fn assert_on_bsp() {
+    let cpu = crate::smp::current_cpu();
+    assert!(cpu == 0, "kernel threads only run on the BSP, not on CPU {}", cpu);
+}
+
+// switch to the next ready thread, leaving the current one in 'state'
+pub(super) fn switch_away(state: ThreadState) {
+    switch_with(|scheduler| scheduler.prepare_switch(state));
+}
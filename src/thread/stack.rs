@@ -0,0 +1,52 @@
+/* thread stacks: heap memory with an unmapped guard page below each */
+
+use super::STACK_SIZE;
+use crate::memory;
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use x86_64::structures::paging::{Page, Size4KiB};
+use x86_64::VirtAddr;
+
+const GUARD_SIZE: usize = 4096;
+
+// an overflow runs into the guard page and faults instead of overwriting the
+// heap below, the double fault handler reports it on its own stack
+pub(super) struct Stack {
+    base: *mut u8, // the guard page, the stack starts above it
+}
+
+// only the thread running on it and the scheduler freeing it touch it
+unsafe impl Send for Stack {}
+
+impl Stack {
+    pub(super) fn new() -> Stack {
+        let layout = Stack::layout();
+        let base = unsafe { alloc(layout) };
+        if base.is_null() {
+            handle_alloc_error(layout);
+        }
+        unsafe { memory::set_present(Stack::guard_page(base), false) };
+        Stack { base }
+    }
+
+    pub(super) fn top(&self) -> u64 {
+        self.base as u64 + (GUARD_SIZE + STACK_SIZE) as u64
+    }
+
+    fn layout() -> Layout {
+        Layout::from_size_align(GUARD_SIZE + STACK_SIZE, GUARD_SIZE).unwrap()
+    }
+
+    fn guard_page(base: *mut u8) -> Page<Size4KiB> {
+        Page::containing_address(VirtAddr::new(base as u64))
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe {
+            // the allocator keeps its bookkeeping in freed memory
+            memory::set_present(Stack::guard_page(self.base), true);
+            dealloc(self.base, Stack::layout());
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/* wait queues: block kernel threads until an event, signalled from threads or interrupt handlers */
+
+use super::scheduler::{self, SCHEDULER};
+use super::ThreadState;
+use x86_64::instructions::interrupts;
+
+// threads blocked on a queue are found through the scheduler's thread table,
+// keyed by the queue's address, so waiting and notifying never allocate
+// a queue must not move while threads wait on it, which borrowing prevents
+pub struct WaitQueue {
+    _unique: u8, // a zero sized queue could share its address with another
+}
+
+impl WaitQueue {
+    pub const fn new() -> WaitQueue {
+        WaitQueue { _unique: 0 }
+    }
+
+    fn key(&self) -> usize {
+        self as *const WaitQueue as usize
+    }
+
+    // block the current thread until the queue is notified
+    // a notification before the call is lost, see wait_until
+    pub fn wait(&self) {
+        interrupts::without_interrupts(|| self.block());
+    }
+
+    // block until 'condition' holds, checked with interrupts disabled so a
+    // notification can't slip in between the check and going to sleep
+    // 'condition' must not take locks held by code running with interrupts enabled
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        interrupts::without_interrupts(|| {
+            while !condition() {
+                self.block();
+            }
+        });
+    }
+
+    // must be called with interrupts disabled
+    fn block(&self) {
+        let key = self.key();
+        scheduler::switch_with(|scheduler| {
+            scheduler.block_current(key);
+            scheduler.prepare_switch(ThreadState::Blocked)
+        });
+    }
+
+    // wake the longest waiting thread, returns whether there was one
+    pub fn notify_one(&self) -> bool {
+        self.notify(false) > 0
+    }
+
+    // wake all waiting threads, returns how many there were
+    pub fn notify_all(&self) -> usize {
+        self.notify(true)
+    }
+
+    // the woken threads run once the scheduler gets to them, so this is
+    // safe to call from interrupt handlers
+    fn notify(&self, all: bool) -> usize {
+        interrupts::without_interrupts(|| {
+            match SCHEDULER.lock().as_mut() {
+                Some(scheduler) => scheduler.wake(self.key(), all),
+                None => 0, // threads are not initialized, nobody can wait
+            }
+        })
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        WaitQueue::new()
+    }
+}
@@ -0,0 +1,94 @@
+/* High Precision Event Timer: a free running counter with a femtosecond period */
+
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::PhysAddr;
+use crate::{acpi, memory};
+
+// registers, as offsets from the base address
+const REG_CAPABILITIES: u64 = 0x00;
+const REG_CONFIGURATION: u64 = 0x10;
+const REG_MAIN_COUNTER: u64 = 0xf0;
+
+const CAP_COUNTER_64BIT: u64 = 1 << 13;
+const CONFIG_ENABLE: u64 = 1;
+
+// the ACPI description table of the HPET
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct HpetTable {
+    header: acpi::SdtHeader,
+    event_timer_block_id: u32,
+    address_space_id: u8, // 0: system memory
+    register_bit_width: u8,
+    register_bit_offset: u8,
+    reserved: u8,
+    address: u64,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+// virtual address of the registers, 0 if there is no usable HPET
+static BASE: AtomicU64 = AtomicU64::new(0);
+static PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+// counter value when the HPET was initialized
+static START: AtomicU64 = AtomicU64::new(0);
+
+unsafe fn read_register(base: u64, offset: u64) -> u64 {
+    ptr::read_volatile((base + offset) as *const u64)
+}
+
+unsafe fn write_register(base: u64, offset: u64, value: u64) {
+    ptr::write_volatile((base + offset) as *mut u64, value)
+}
+
+// find the HPET through ACPI and start its main counter
+// returns false if there is none or it only has a 32-bit counter
+pub fn init() -> bool {
+    let table: HpetTable = match acpi::find_table(b"HPET") {
+        Some(addr) => unsafe { acpi::read(addr) },
+        None => return false,
+    };
+    if table.address_space_id != 0 {
+        return false;
+    }
+
+    let base = memory::phys_to_virt(PhysAddr::new(table.address)).as_u64();
+    unsafe {
+        let capabilities = read_register(base, REG_CAPABILITIES);
+        if capabilities & CAP_COUNTER_64BIT == 0 {
+            return false;
+        }
+        PERIOD_FS.store(capabilities >> 32, Ordering::Relaxed);
+
+        let configuration = read_register(base, REG_CONFIGURATION);
+        write_register(base, REG_CONFIGURATION, configuration | CONFIG_ENABLE);
+        START.store(read_register(base, REG_MAIN_COUNTER), Ordering::Relaxed);
+    }
+    BASE.store(base, Ordering::Release);
+    true
+}
+
+pub fn is_available() -> bool {
+    BASE.load(Ordering::Acquire) != 0
+}
+
+// raw value of the main counter
+pub fn counter() -> Option<u64> {
+    match BASE.load(Ordering::Acquire) {
+        0 => None,
+        base => Some(unsafe { read_register(base, REG_MAIN_COUNTER) }),
+    }
+}
+
+// length of one counter tick in femtoseconds
+pub fn period_fs() -> u64 {
+    PERIOD_FS.load(Ordering::Relaxed)
+}
+
+// nanoseconds since init
+pub fn nanos() -> Option<u64> {
+    let ticks = counter()? - START.load(Ordering::Relaxed);
+    Some((u128::from(ticks) * u128::from(period_fs()) / 1_000_000) as u64)
+}
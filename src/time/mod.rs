@@ -0,0 +1,110 @@
+/* timekeeping: the PIT tick, the HPET and a calibrated TSC behind one monotonic clock */
+
+pub mod pit;
+pub mod hpet;
+pub mod tsc;
+
+use core::hint;
+use core::time::Duration;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// frequency of the timer interrupt
+pub const TICK_HZ: u32 = 100;
+const NANOS_PER_TICK: u64 = 1_000_000_000 / TICK_HZ as u64;
+
+// timer interrupts since boot
+static TICKS: AtomicU64 = AtomicU64::new(0);
+// monotonic time when the fine grained clock took over from the tick
+static OFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+static SOURCE: AtomicU8 = AtomicU8::new(ClockSource::Pit as u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ClockSource {
+    Pit = 0, // timer interrupt count, TICK_HZ resolution
+    Hpet,
+    Tsc,
+}
+
+// Called by the timer interrupt handler
+pub(crate) fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+// timer interrupts since boot
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// find the HPET, calibrate the TSC and pick the best clock source:
+// an invariant TSC, then the HPET, then the timer tick
+// memory::init must have been called before since the HPET is found through ACPI
+pub fn init() {
+    let hpet = hpet::init();
+    tsc::calibrate();
+
+    let source = if tsc::is_invariant() {
+        ClockSource::Tsc
+    } else if hpet {
+        ClockSource::Hpet
+    } else {
+        ClockSource::Pit
+    };
+    OFFSET_NANOS.store(ticks() * NANOS_PER_TICK, Ordering::Relaxed);
+    SOURCE.store(source as u8, Ordering::Release);
+}
+
+pub fn source() -> ClockSource {
+    match SOURCE.load(Ordering::Acquire) {
+        2 => ClockSource::Tsc,
+        1 => ClockSource::Hpet,
+        _ => ClockSource::Pit,
+    }
+}
+
+// nanoseconds since boot, never going backwards
+pub fn monotonic_nanos() -> u64 {
+    let fine = match source() {
+        ClockSource::Tsc => tsc::nanos(),
+        ClockSource::Hpet => hpet::nanos(),
+        ClockSource::Pit => None,
+    };
+    match fine {
+        Some(nanos) => OFFSET_NANOS.load(Ordering::Relaxed) + nanos,
+        None => ticks() * NANOS_PER_TICK,
+    }
+}
+
+pub fn uptime() -> Duration {
+    Duration::from_nanos(monotonic_nanos())
+}
+
+// busy-wait for at least 'nanos' nanoseconds
+// falls back to polling the PIT when there is no fine grained clock,
+// so it also works with interrupts disabled
+pub fn delay_ns(nanos: u64) {
+    if source() == ClockSource::Pit {
+        pit::wait_us((nanos + 999) / 1000);
+        return;
+    }
+    let deadline = monotonic_nanos() + nanos;
+    while monotonic_nanos() < deadline {
+        hint::spin_loop();
+    }
+}
+
+pub fn delay_us(us: u64) {
+    delay_ns(us * 1000);
+}
+
+pub fn delay_ms(ms: u64) {
+    delay_ns(ms * 1_000_000);
+}
+
+#[test_case]
+fn test_monotonic_clock_advances() {
+    // the tick alone only has a 10 ms resolution
+    let start = monotonic_nanos();
+    delay_ms(20);
+    assert!(monotonic_nanos() > start);
+}
@@ -0,0 +1,59 @@
+/* Programmable Interval Timer: the periodic tick on IRQ 0 and short waits on channel 2 */
+
+use x86_64::instructions::port::Port;
+
+// input clock of the PIT in Hz
+pub const FREQUENCY: u32 = 1_193_182;
+
+const CHANNEL_0: u16 = 0x40;
+const CHANNEL_2: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+const SPEAKER: u16 = 0x61; // bit 0 gates channel 2, bit 5 reflects its output
+
+// channel, lobyte/hibyte access and operating mode
+const CMD_CHANNEL_0_SQUARE_WAVE: u8 = 0b0011_0110; // mode 3
+const CMD_CHANNEL_2_ONE_SHOT: u8 = 0b1011_0000;    // mode 0
+
+// program channel 0 to raise IRQ 0 'hz' times per second
+pub fn set_frequency(hz: u32) {
+    let divisor = (FREQUENCY / hz).clamp(1, 0xffff) as u16;
+    let mut command: Port<u8> = Port::new(COMMAND);
+    let mut data: Port<u8> = Port::new(CHANNEL_0);
+
+    unsafe {
+        command.write(CMD_CHANNEL_0_SQUARE_WAVE);
+        data.write(divisor as u8);
+        data.write((divisor >> 8) as u8);
+    }
+}
+
+// busy-wait for 'ticks' PIT input clock cycles (0xffff is about 55 ms)
+// channel 2 is polled, so this also works with interrupts disabled
+pub fn wait_ticks(ticks: u16) {
+    let mut command: Port<u8> = Port::new(COMMAND);
+    let mut data: Port<u8> = Port::new(CHANNEL_2);
+    let mut speaker: Port<u8> = Port::new(SPEAKER);
+
+    unsafe {
+        // keep the gate low and the speaker off while loading the count
+        let control = speaker.read() & !0b11;
+        speaker.write(control);
+        command.write(CMD_CHANNEL_2_ONE_SHOT);
+        data.write(ticks as u8);
+        data.write((ticks >> 8) as u8);
+        // raising the gate starts the count down, the output goes high at zero
+        speaker.write(control | 1);
+        while speaker.read() & 0x20 == 0 {}
+        speaker.write(control);
+    }
+}
+
+// busy-wait for the given number of microseconds
+pub fn wait_us(us: u64) {
+    let mut ticks = us * u64::from(FREQUENCY) / 1_000_000;
+    while ticks > 0 {
+        let chunk = ticks.min(0xffff);
+        wait_ticks(chunk as u16);
+        ticks -= chunk;
+    }
+}
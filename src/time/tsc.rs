@@ -0,0 +1,68 @@
+/* Time Stamp Counter: calibrated against the HPET or the PIT */
+
+use core::arch::x86_64::{__cpuid, _rdtsc};
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+use super::{hpet, pit};
+
+// length of the calibration window
+const CALIBRATION_US: u64 = 10_000;
+
+// TSC frequency in Hz, 0 until calibrated
+static FREQUENCY: AtomicU64 = AtomicU64::new(0);
+// TSC value at calibration
+static START: AtomicU64 = AtomicU64::new(0);
+
+// read the CPU time stamp counter
+pub fn read() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+// an invariant TSC runs at a constant rate in all power states
+pub fn is_invariant() -> bool {
+    unsafe {
+        __cpuid(0x8000_0000).eax >= 0x8000_0007
+            && __cpuid(0x8000_0007).edx & (1 << 8) != 0
+    }
+}
+
+// measure the TSC frequency, preferring the HPET as reference
+pub fn calibrate() -> u64 {
+    let hz = interrupts::without_interrupts(|| {
+        match hpet::nanos() {
+            Some(start_ns) => {
+                let start = read();
+                let mut elapsed_ns = 0;
+                while elapsed_ns < CALIBRATION_US * 1000 {
+                    elapsed_ns = hpet::nanos().unwrap() - start_ns;
+                }
+                (read() - start) * 1_000_000_000 / elapsed_ns
+            }
+            None => {
+                let start = read();
+                pit::wait_us(CALIBRATION_US);
+                (read() - start) * 1_000_000 / CALIBRATION_US
+            }
+        }
+    });
+
+    START.store(read(), Ordering::Relaxed);
+    FREQUENCY.store(hz, Ordering::Release);
+    hz
+}
+
+// TSC frequency in Hz, None if not calibrated yet
+pub fn frequency() -> Option<u64> {
+    match FREQUENCY.load(Ordering::Acquire) {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+// nanoseconds since calibration
+pub fn nanos() -> Option<u64> {
+    let hz = frequency()?;
+    // another CPU's TSC may lag behind the one calibrated on
+    let cycles = read().saturating_sub(START.load(Ordering::Relaxed));
+    Some((u128::from(cycles) * 1_000_000_000 / u128::from(hz)) as u64)
+}
@@ -0,0 +1,203 @@
+/* ANSI/VT100 escape sequences, parsed into actions for the writer */
+
+// parameters beyond this are ignored
+pub(super) const MAX_PARAMS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Params {
+	values: [u16; MAX_PARAMS],
+	len: usize,
+}
+
+impl Params {
+	const fn new() -> Params {
+		Params { values: [0; MAX_PARAMS], len: 0 }
+	}
+
+	// parameter 'index', 'default' when missing or 0
+	pub(super) fn get(&self, index: usize, default: u16) -> u16 {
+		match self.values[..self.len].get(index) {
+			Some(&value) if value != 0 => value,
+			_ => default,
+		}
+	}
+
+	pub(super) fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+		self.values[..self.len].iter().copied()
+	}
+
+	pub(super) fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Action {
+	Print(char),
+	Sgr(Params), // select graphic rendition: colors and intensity
+	CursorUp(u16),
+	CursorDown(u16),
+	CursorForward(u16),
+	CursorBack(u16),
+	CursorPosition(u16, u16), // row and column, counted from 1
+	CursorColumn(u16), // counted from 1
+	EraseScreen(u16), // 0: to the end, 1: from the start, 2: all
+	EraseLine(u16), // same as EraseScreen, within the row
+	SaveCursor,
+	RestoreCursor,
+	ShowCursor(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+	Ground,
+	Escape, // after ESC
+	Csi { private: bool }, // after ESC [, 'private' after ESC [ ?
+}
+
+pub(super) struct Parser {
+	state: State,
+	params: Params,
+}
+
+impl Parser {
+	pub(super) const fn new() -> Parser {
+		Parser { state: State::Ground, params: Params::new() }
+	}
+
+	// feed one character, returns what to do once a sequence is complete
+	pub(super) fn advance(&mut self, ch: char) -> Option<Action> {
+		match self.state {
+			State::Ground => match ch {
+				'\u{1b}' => {
+					self.state = State::Escape;
+					None
+				}
+				'\n' | '\t' => Some(Action::Print(ch)),
+				'\r' => Some(Action::CursorColumn(1)),
+				'\u{8}' => Some(Action::CursorBack(1)),
+				// BEL and the other control codes have no glyph to show
+				'\0'..='\u{1f}' | '\u{7f}' => None,
+				_ => Some(Action::Print(ch)),
+			},
+			State::Escape => {
+				self.state = State::Ground;
+				match ch {
+					'[' => {
+						self.params = Params::new();
+						self.state = State::Csi { private: false };
+						None
+					}
+					'7' => Some(Action::SaveCursor),
+					'8' => Some(Action::RestoreCursor),
+					'c' => Some(Action::EraseScreen(2)), // reset
+					_ => None,
+				}
+			}
+			State::Csi { private } => self.csi(ch, private),
+		}
+	}
+
+	fn csi(&mut self, ch: char, private: bool) -> Option<Action> {
+		let params = &mut self.params;
+		match ch {
+			'0'..='9' => {
+				if params.len == 0 {
+					params.len = 1;
+				}
+				if let Some(value) = params.values.get_mut(params.len - 1) {
+					let digit = ch as u16 - '0' as u16;
+					*value = value.saturating_mul(10).saturating_add(digit);
+				}
+				return None;
+			}
+			';' => {
+				// an empty parameter before the separator counts as 0
+				if params.len == 0 {
+					params.len = 1;
+				}
+				if params.len < MAX_PARAMS {
+					params.len += 1;
+				}
+				return None;
+			}
+			'?' if params.is_empty() => {
+				self.state = State::Csi { private: true };
+				return None;
+			}
+			_ => {}
+		}
+
+		// anything else ends the sequence
+		self.state = State::Ground;
+		let params = self.params;
+		if private {
+			// only cursor visibility, ESC [ ? 25 h / l
+			return match (ch, params.get(0, 0)) {
+				('h', 25) => Some(Action::ShowCursor(true)),
+				('l', 25) => Some(Action::ShowCursor(false)),
+				_ => None,
+			};
+		}
+		let n = params.get(0, 1);
+		match ch {
+			'm' => Some(Action::Sgr(params)),
+			'A' => Some(Action::CursorUp(n)),
+			'B' => Some(Action::CursorDown(n)),
+			'C' => Some(Action::CursorForward(n)),
+			'D' => Some(Action::CursorBack(n)),
+			'H' | 'f' => Some(Action::CursorPosition(n, params.get(1, 1))),
+			'G' => Some(Action::CursorColumn(n)),
+			'J' => Some(Action::EraseScreen(params.get(0, 0))),
+			'K' => Some(Action::EraseLine(params.get(0, 0))),
+			's' => Some(Action::SaveCursor),
+			'u' => Some(Action::RestoreCursor),
+			_ => None,
+		}
+	}
+}
+
+// the VGA color index of ANSI color 'index' (0 to 7)
+// ANSI counts black, red, green, yellow, blue, magenta, cyan, white
+pub(super) fn vga_color(index: u16) -> u8 {
+	const TABLE: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+	TABLE[usize::from(index & 7)]
+}
+
+#[cfg(test)]
+fn parse(parser: &mut Parser, s: &str) -> Option<Action> {
+	let mut last = None;
+	for ch in s.chars() {
+		last = parser.advance(ch);
+	}
+	last
+}
+
+#[test_case]
+fn test_parse_sequences() {
+	let mut parser = Parser::new();
+	assert_eq!(parser.advance('a'), Some(Action::Print('a')));
+	assert_eq!(parse(&mut parser, "\x1b[5A"), Some(Action::CursorUp(5)));
+	assert_eq!(parse(&mut parser, "\x1b[C"), Some(Action::CursorForward(1)));
+	assert_eq!(parse(&mut parser, "\x1b[3;12H"), Some(Action::CursorPosition(3, 12)));
+	assert_eq!(parse(&mut parser, "\x1b[;7H"), Some(Action::CursorPosition(1, 7)));
+	assert_eq!(parse(&mut parser, "\x1b[2J"), Some(Action::EraseScreen(2)));
+	assert_eq!(parse(&mut parser, "\x1b[K"), Some(Action::EraseLine(0)));
+	assert_eq!(parse(&mut parser, "\x1b[?25l"), Some(Action::ShowCursor(false)));
+	assert_eq!(parse(&mut parser, "\x1b7"), Some(Action::SaveCursor));
+	match parse(&mut parser, "\x1b[1;31m") {
+		Some(Action::Sgr(params)) => {
+			assert_eq!(params.iter().collect::<alloc::vec::Vec<_>>(), [1, 31]);
+		}
+		other => panic!("expected SGR, got {:?}", other),
+	}
+	// back to printing after the sequence
+	assert_eq!(parser.advance('b'), Some(Action::Print('b')));
+	// control codes move the cursor or are dropped, never printed
+	assert_eq!(parser.advance('\r'), Some(Action::CursorColumn(1)));
+	assert_eq!(parser.advance('\u{8}'), Some(Action::CursorBack(1)));
+	assert_eq!(parser.advance('\n'), Some(Action::Print('\n')));
+	assert_eq!(parser.advance('\u{7}'), None);
+	assert_eq!(parser.advance('\0'), None);
+	assert_eq!(parser.advance('c'), Some(Action::Print('c')));
+}
@@ -0,0 +1,39 @@
+/* the attribute controller, which decides what bit 7 of a cell's color means */
+
+use x86_64::instructions::port::Port;
+
+// index and data share a port, a read of the input status register resets
+// it to expect the index
+const ADDRESS_DATA: u16 = 0x3C0;
+const DATA_READ: u16 = 0x3C1;
+const INPUT_STATUS_1: u16 = 0x3DA;
+
+const MODE_CONTROL: u8 = 0x10;
+const MODE_BLINK: u8 = 0x08;
+// set with the index, or the screen goes blank while the palette is unlocked
+const PALETTE_ADDRESS_SOURCE: u8 = 0x20;
+
+fn select(register: u8) {
+	unsafe {
+		Port::<u8>::new(INPUT_STATUS_1).read();
+		Port::<u8>::new(ADDRESS_DATA).write(register | PALETTE_ADDRESS_SOURCE);
+	}
+}
+
+// with blinking on, bit 7 of the color blinks the character, with it off
+// bit 7 brightens the background, giving 16 background colors
+pub(super) fn set_blink_enabled(enabled: bool) {
+	select(MODE_CONTROL);
+	let mode = unsafe { Port::<u8>::new(DATA_READ).read() };
+	let mode = if enabled { mode | MODE_BLINK } else { mode & !MODE_BLINK };
+	// the read left the port expecting data
+	unsafe { Port::<u8>::new(ADDRESS_DATA).write(mode) };
+}
+
+pub(super) fn is_blink_enabled() -> bool {
+	select(MODE_CONTROL);
+	let mode = unsafe { Port::<u8>::new(DATA_READ).read() };
+	// back to expecting an index
+	unsafe { Port::<u8>::new(INPUT_STATUS_1).read() };
+	mode & MODE_BLINK != 0
+}
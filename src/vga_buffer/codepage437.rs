@@ -1,5 +1,36 @@
+/* Code Page 437, the character set of the VGA text mode */
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// the character of each byte, encode is its inverse
+const TABLE: [char; 256] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+// what characters without a byte are shown as, ■ by default
+static REPLACEMENT: AtomicU8 = AtomicU8::new(0xfe);
+
+// the byte showing 'c', None if Code Page 437 has no such character
 pub fn encode(c: char) -> Option<u8> {
-    let b = match c {
+    let byte = match c {
+        ' '..='~' => c as u8,
+
+        '\0' => 0x00,
         '☺' => 0x01,
         '☻' => 0x02,
         '♥' => 0x03,
@@ -33,23 +64,23 @@ pub fn encode(c: char) -> Option<u8> {
         '▲' => 0x1e,
         '▼' => 0x1f,
 
-        ' '..='~' => c as u8,
         '⌂' => 0x7f,
-        'Δ' => 0x7f,
+        'Δ' => 0x7f, // looks like '⌂'
 
         'Ç' => 0x80,
         'ü' => 0x81,
         'é' => 0x82,
         'â' => 0x83,
         'ä' => 0x84,
-        'å' => 0x85,
-        'ç' => 0x86,
-        'ê' => 0x87,
-        'ë' => 0x88,
-        'è' => 0x89,
-        'ï' => 0x8a,
-        'î' => 0x8b,
-        'ì' => 0x8c,
+        'à' => 0x85,
+        'å' => 0x86,
+        'ç' => 0x87,
+        'ê' => 0x88,
+        'ë' => 0x89,
+        'è' => 0x8a,
+        'ï' => 0x8b,
+        'î' => 0x8c,
+        'ì' => 0x8d,
         'Ä' => 0x8e,
         'Å' => 0x8f,
 
@@ -96,14 +127,14 @@ pub fn encode(c: char) -> Option<u8> {
         '╢' => 0xb6,
         '╖' => 0xb7,
         '╕' => 0xb8,
-        '╣' => 0xb9, 
+        '╣' => 0xb9,
         '║' => 0xba,
         '╗' => 0xbb,
         '╝' => 0xbc,
         '╜' => 0xbd,
         '╛' => 0xbe,
         '┐' => 0xbf,
-        
+
         '└' => 0xc0,
         '┴' => 0xc1,
         '┬' => 0xc2,
@@ -140,13 +171,13 @@ pub fn encode(c: char) -> Option<u8> {
 
         'α' => 0xe0,
         'ß' => 0xe1,
-        'β' => 0xe1,
+        'β' => 0xe1, // looks like 'ß'
         'Γ' => 0xe2,
         'π' => 0xe3,
-        'Π' => 0xe3,
-        '∏' => 0xe3,
+        'Π' => 0xe3, // looks like 'π'
+        '∏' => 0xe3, // looks like 'π'
         'Σ' => 0xe4,
-        '∑' => 0xe4,
+        '∑' => 0xe4, // looks like 'Σ'
         'σ' => 0xe5,
         'µ' => 0xe6,
         'τ' => 0xe7,
@@ -154,15 +185,15 @@ pub fn encode(c: char) -> Option<u8> {
         'Θ' => 0xe9,
         'Ω' => 0xea,
         'δ' => 0xeb,
-        'ð' => 0xeb,
-        '∂' => 0xeb,
+        'ð' => 0xeb, // looks like 'δ'
+        '∂' => 0xeb, // looks like 'δ'
         '∞' => 0xec,
         'φ' => 0xed,
-        'ϕ' => 0xed,
-        '𝜙' => 0xed,
+        'ϕ' => 0xed, // looks like 'φ'
+        '𝜙' => 0xed, // looks like 'φ'
         'ε' => 0xee,
-        '∈' => 0xee,
-        '€' => 0xee,
+        '∈' => 0xee, // looks like 'ε'
+        '€' => 0xee, // looks like 'ε'
         '∩' => 0xef,
 
         '≡' => 0xf0,
@@ -177,16 +208,75 @@ pub fn encode(c: char) -> Option<u8> {
         '∙' => 0xf9,
         '·' => 0xfa,
         '√' => 0xfb,
-        '✓' => 0xfb,
+        '✓' => 0xfb, // looks like '√'
         'ⁿ' => 0xfc,
         '²' => 0xfd,
         '■' => 0xfe,
-
-        _ => 0x00,
+        '\u{a0}' => 0xff,
+        _ => return None,
     };
-    if b == 0 {
-        None
-    } else {
-        Some(b)
+    Some(byte)
+}
+
+// the character byte 'byte' shows
+pub fn decode(byte: u8) -> char {
+    TABLE[usize::from(byte)]
+}
+
+// 'c', or the replacement byte if it has none
+pub fn encode_or_replace(c: char) -> u8 {
+    encode(c).unwrap_or_else(|| REPLACEMENT.load(Ordering::Relaxed))
+}
+
+// show characters without a byte as 'c' from now on
+// returns false, changing nothing, if 'c' has no byte itself
+pub fn set_replacement(c: char) -> bool {
+    match encode(c) {
+        Some(byte) => {
+            REPLACEMENT.store(byte, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn replacement() -> char {
+    decode(REPLACEMENT.load(Ordering::Relaxed))
+}
+
+#[test_case]
+fn test_table_is_bijective() {
+    for byte in 0..=u8::MAX {
+        assert_eq!(encode(decode(byte)), Some(byte));
     }
-} 
+    // characters that only look like a table entry are encoded, never decoded to
+    let aliases = [
+        ('Δ', 0x7f),
+        ('β', 0xe1),
+        ('Π', 0xe3),
+        ('∏', 0xe3),
+        ('∑', 0xe4),
+        ('ð', 0xeb),
+        ('∂', 0xeb),
+        ('ϕ', 0xed),
+        ('𝜙', 0xed),
+        ('∈', 0xee),
+        ('€', 0xee),
+        ('✓', 0xfb),
+    ];
+    for &(alias, byte) in aliases.iter() {
+        assert_eq!(encode(alias), Some(byte));
+        assert_ne!(decode(byte), alias);
+    }
+}
+
+#[test_case]
+fn test_replacement() {
+    assert_eq!(encode('✈'), None);
+    assert_eq!(encode_or_replace('✈'), 0xfe);
+    assert!(!set_replacement('✈'));
+    assert!(set_replacement('?'));
+    assert_eq!(encode_or_replace('✈'), b'?');
+    assert_eq!(replacement(), '?');
+    assert!(set_replacement('■'));
+}
@@ -0,0 +1,201 @@
+/* virtual consoles: several writers, only the active one is on the screen */
+
+use super::{ScreenChar, Writer, BUFFER_HEIGHT, BUFFER_WIDTH, DEFAULT_COLOR, DEFAULT_SCROLLBACK_LEN, WRITER};
+use crate::task::channel::broadcast::RecvError;
+use crate::task::keyboard::{self, KeyCode};
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+pub const CONSOLE_COUNT: usize = 6;
+// the console a terminal on COM1 is attached to
+pub const SERIAL_CONSOLE: usize = 0;
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+// held while switching, so two switches don't interleave
+static SWITCHING: Mutex<()> = Mutex::new(());
+
+lazy_static! {
+	// consoles 1 and up, console 0 is WRITER
+	// created on first use, which must come after the heap
+	static ref OTHERS: Vec<Mutex<Writer>> = (1..CONSOLE_COUNT)
+		.map(|_| {
+			let blank = ScreenChar {
+				ascii_character: b' ',
+				color_code: DEFAULT_COLOR,
+			};
+			let mut writer = Writer {
+				row_position: 0,
+				column_position: 0,
+				color_code: DEFAULT_COLOR,
+				default_color: DEFAULT_COLOR,
+				// only the active console has the screen, the others keep
+				// to their shadow
+				screen: None,
+				ansi: super::ansi::Parser::new(),
+				bold: false,
+				saved_position: (0, 0),
+				scrollback: None,
+				shadow: Some(Box::new([[blank; BUFFER_WIDTH]; BUFFER_HEIGHT])),
+				cursor_visible: true,
+				cursor_shape: None,
+			};
+			writer.set_scrollback_len(DEFAULT_SCROLLBACK_LEN);
+			Mutex::new(writer)
+		})
+		.collect();
+}
+
+// the writer of console 'index', panics if there is no such console
+pub fn console(index: usize) -> &'static Mutex<Writer> {
+	assert!(index < CONSOLE_COUNT, "no console {}", index);
+	match index {
+		0 => &WRITER,
+		_ => &OTHERS[index - 1],
+	}
+}
+
+// the console on the screen
+pub fn active() -> usize {
+	ACTIVE.load(Ordering::Relaxed)
+}
+
+pub fn active_console() -> &'static Mutex<Writer> {
+	console(active())
+}
+
+// put console 'index' on the screen
+pub fn switch_to(index: usize) {
+	assert!(index < CONSOLE_COUNT, "no console {}", index);
+	interrupts::without_interrupts(|| {
+		let _switching = SWITCHING.lock();
+		let previous = active();
+		if previous == index {
+			return;
+		}
+		// the writes in between go to the shadows of both
+		let screen = console(previous).lock().deactivate()
+			.expect("the active console has the screen");
+		ACTIVE.store(index, Ordering::Relaxed);
+		console(index).lock().activate(screen);
+	});
+}
+
+// put console 0 back on the screen for a panic message, returns the console
+// on the screen afterwards; only tries the locks, the panicking CPU may be
+// the one holding them
+pub fn switch_for_panic() -> usize {
+	interrupts::without_interrupts(|| {
+		let previous = active();
+		if previous == 0 {
+			return 0;
+		}
+		let _switching = match SWITCHING.try_lock() {
+			Some(guard) => guard,
+			None => return previous,
+		};
+		let (mut from, mut to) = match (console(previous).try_lock(), WRITER.try_lock()) {
+			(Some(from), Some(to)) => (from, to),
+			_ => return previous,
+		};
+		match from.deactivate() {
+			Some(screen) => {
+				ACTIVE.store(0, Ordering::Relaxed);
+				to.activate(screen);
+				0
+			}
+			None => previous,
+		}
+	})
+}
+
+// print to console 'index', whether it is on the screen or not
+pub fn print_to(index: usize, args: fmt::Arguments) {
+	use core::fmt::Write;
+	interrupts::without_interrupts(|| {
+		console(index).lock().write_fmt(args).unwrap()
+	});
+}
+
+// the console switching task: Alt+F1 to Alt+F6 show console 0 to 5
+pub async fn run() {
+	let mut keys = keyboard::subscribe();
+	loop {
+		match keys.recv().await {
+			Ok(event) if event.pressed && event.modifiers.alt => {
+				let index = match event.code {
+					KeyCode::F1 => 0,
+					KeyCode::F2 => 1,
+					KeyCode::F3 => 2,
+					KeyCode::F4 => 3,
+					KeyCode::F5 => 4,
+					KeyCode::F6 => 5,
+					_ => continue,
+				};
+				switch_to(index);
+			}
+			Ok(_) | Err(RecvError::Lagged(_)) => continue,
+			Err(RecvError::Closed) => break,
+		}
+	}
+}
+
+#[test_case]
+fn test_inactive_console_keeps_to_its_shadow() {
+	print_to(2, format_args!("on two"));
+	interrupts::without_interrupts(|| {
+		assert_eq!(console(2).lock().read_cell(0, 0).ascii_character, b'o');
+	});
+
+	switch_to(2);
+	assert_eq!(active(), 2);
+	// console 0 keeps its output while hidden
+	print_to(0, format_args!("\nhidden"));
+	let row = interrupts::without_interrupts(|| {
+		assert_eq!(console(2).lock().screen_cell(0, 3).ascii_character, b't');
+		let writer = WRITER.lock();
+		let (row, _) = writer.position();
+		assert_eq!(writer.read_cell(row, 0).ascii_character, b'h');
+		row
+	});
+
+	switch_to(0);
+	interrupts::without_interrupts(|| {
+		assert_eq!(WRITER.lock().screen_cell(row, 0).ascii_character, b'h');
+	});
+}
+
+#[test_case]
+fn test_switch_for_panic_shows_console_0() {
+	switch_to(3);
+	assert_eq!(switch_for_panic(), 0);
+	assert_eq!(active(), 0);
+	// a console that is locked stays where it is
+	switch_to(3);
+	interrupts::without_interrupts(|| {
+		let _locked = console(3).lock();
+		assert_eq!(switch_for_panic(), 3);
+	});
+	switch_to(0);
+}
+
+#[test_case]
+fn test_cursor_settings_follow_the_console() {
+	use super::cursor;
+	let on_screen = interrupts::without_interrupts(cursor::state);
+	// a hidden console keeps its settings to itself
+	interrupts::without_interrupts(|| {
+		let mut writer = console(4).lock();
+		writer.hide_cursor();
+		writer.set_cursor_shape(0, 15);
+		assert_eq!(cursor::state(), on_screen);
+	});
+
+	switch_to(4);
+	assert_eq!(interrupts::without_interrupts(cursor::state), (false, (0, 15)));
+	switch_to(0);
+	assert_eq!(interrupts::without_interrupts(cursor::state), on_screen);
+}
@@ -0,0 +1,73 @@
+/* the blinking hardware cursor, programmed through the CRT controller */
+
+use conquer_once::spin::OnceCell;
+use x86_64::instructions::port::Port;
+
+// the CRTC is reached through an index and a data port
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+
+const CURSOR_START: u8 = 0x0A; // bit 5 disables the cursor
+const CURSOR_END: u8 = 0x0B;
+const LOCATION_HIGH: u8 = 0x0E;
+const LOCATION_LOW: u8 = 0x0F;
+
+const CURSOR_DISABLE: u8 = 0x20;
+const SCANLINE_MASK: u8 = 0x1F;
+
+// scanlines of a character cell, the shape is a range within them
+pub const MAX_SCANLINE: u8 = 15;
+
+// the shape before the first change, for consoles that never set one
+static BOOT_SHAPE: OnceCell<(u8, u8)> = OnceCell::uninit();
+
+fn read(register: u8) -> u8 {
+	unsafe {
+		Port::<u8>::new(CRTC_INDEX).write(register);
+		Port::<u8>::new(CRTC_DATA).read()
+	}
+}
+
+fn write(register: u8, value: u8) {
+	unsafe {
+		Port::<u8>::new(CRTC_INDEX).write(register);
+		Port::<u8>::new(CRTC_DATA).write(value);
+	}
+}
+
+// move the cursor to cell 'offset', counted row by row from the top left
+pub(super) fn set_offset(offset: u16) {
+	write(LOCATION_HIGH, (offset >> 8) as u8);
+	write(LOCATION_LOW, offset as u8);
+}
+
+pub(super) fn set_visible(visible: bool) {
+	let start = read(CURSOR_START);
+	let start = if visible { start & !CURSOR_DISABLE } else { start | CURSOR_DISABLE };
+	write(CURSOR_START, start);
+}
+
+fn shape() -> (u8, u8) {
+	(read(CURSOR_START) & SCANLINE_MASK, read(CURSOR_END) & SCANLINE_MASK)
+}
+
+// what the hardware shows: whether the cursor is visible, and its shape
+#[cfg(test)]
+pub(super) fn state() -> (bool, (u8, u8)) {
+	(read(CURSOR_START) & CURSOR_DISABLE == 0, shape())
+}
+
+pub(super) fn boot_shape() -> (u8, u8) {
+	let _ = BOOT_SHAPE.try_init_once(shape);
+	*BOOT_SHAPE.try_get().expect("initialized above")
+}
+
+// the cursor covers scanlines 'start' to 'end' of the cell, a block cursor is 0..=15
+pub(super) fn set_shape((start, end): (u8, u8)) {
+	let _ = BOOT_SHAPE.try_init_once(shape);
+	// keep the disable bit and the skew bits above the scanline
+	let visible = read(CURSOR_START) & !SCANLINE_MASK;
+	write(CURSOR_START, visible | (start & SCANLINE_MASK));
+	let skew = read(CURSOR_END) & !SCANLINE_MASK;
+	write(CURSOR_END, skew | (end & SCANLINE_MASK));
+}
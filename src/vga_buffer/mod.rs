@@ -1,7 +1,18 @@
 /* Global println function implementation */
 
 // special character
-mod codepage437;
+pub mod codepage437;
+mod cursor;
+mod attribute;
+mod ansi;
+mod scrollback;
+pub mod console;
+
+pub use scrollback::DEFAULT_SCROLLBACK_LEN;
+
+use ansi::Action;
+use scrollback::Scrollback;
+use alloc::{boxed::Box, string::String};
 
 use core::fmt; // support formatting macros
 use spin::Mutex; // set spinlock to imeplement safety interior mutability
@@ -32,6 +43,29 @@ pub enum Color {
 	White = 15,
 }
 
+impl Color {
+	const ALL: [Color; 16] = [
+		Color::Black, Color::Blue, Color::Green, Color::Cyan,
+		Color::Red, Color::Magenta, Color::Brown, Color::LightGray,
+		Color::DarkGray, Color::LightBlue, Color::LightGreen, Color::LightCyan,
+		Color::LightRed, Color::Pink, Color::Yellow, Color::White,
+	];
+
+	// the color with VGA index 'index', only the low 4 bits count
+	pub fn from_index(index: u8) -> Color {
+		Color::ALL[usize::from(index & 0x0F)]
+	}
+
+	// the light variant, e.g. LightRed for Red
+	pub fn bright(self) -> Color {
+		Color::from_index(self as u8 | 8)
+	}
+
+	pub fn is_bright(self) -> bool {
+		self as u8 & 8 != 0
+	}
+}
+
 // character color
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
@@ -41,8 +75,32 @@ impl ColorCode {
 	fn new(foreground: Color, background: Color) -> ColorCode {
 		ColorCode((background as u8) << 4 | (foreground as u8))
 	}
+
+	// the same code with another foreground or background, as VGA color index
+	fn with_foreground(self, foreground: u8) -> ColorCode {
+		ColorCode(self.0 & 0xF0 | foreground & 0x0F)
+	}
+
+	fn with_background(self, background: u8) -> ColorCode {
+		ColorCode((background & 0x0F) << 4 | self.0 & 0x0F)
+	}
+
+	fn foreground(self) -> u8 {
+		self.0 & 0x0F
+	}
+
+	fn background(self) -> u8 {
+		self.0 >> 4
+	}
+
+	fn with_blink(self, blink: bool) -> ColorCode {
+		ColorCode(if blink { self.0 | 0x80 } else { self.0 & 0x7F })
+	}
 }
 
+// the colors a console starts with
+const DEFAULT_COLOR: ColorCode = ColorCode((Color::Black as u8) << 4 | Color::Green as u8);
+
 // character
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
@@ -51,69 +109,513 @@ struct ScreenChar {
 	color_code: ColorCode,
 }
 
-const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+pub(crate) const BUFFER_HEIGHT: usize = 25;
+pub(crate) const BUFFER_WIDTH: usize = 80;
 
 // buffer struct
 struct Buffer {
 	chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+// a row of cells kept in memory, off the screen
+type Row = [ScreenChar; BUFFER_WIDTH];
+
 // write character to screen
 pub struct Writer {
+	row_position: usize, // row written to
 	column_position: usize, // cursor position
 	color_code: ColorCode, // color
-	buffer: &'static mut Buffer, // 'static lifetime
+	default_color: ColorCode, // what SGR 0, 39 and 49 go back to, follows the setters
+	screen: Option<&'static mut Buffer>, // the VGA buffer, held only while on the screen
+	ansi: ansi::Parser, // escape sequences may span several writes
+	bold: bool, // SGR 1, brightens the foreground colors set after it
+	saved_position: (usize, usize),
+	scrollback: Option<Scrollback>, // None until the heap is there
+	shadow: Option<Box<[Row; BUFFER_HEIGHT]>>, // the cells of an inactive console
+	cursor_visible: bool,
+	cursor_shape: Option<(u8, u8)>, // None until set, the shape the BIOS chose
 }
 
 impl Writer {
 	// print character
 	pub fn write_char(&mut self, character:char) {
+		self.put_char(character);
+		self.update_cursor();
+	}
+
+	// print byte
+	pub fn write_byte(&mut self, byte: u8) {
+		self.put_byte(byte);
+		self.update_cursor();
+	}
+
+	// print string
+	pub fn write_string(&mut self, s: &str) {
+		for c in s.chars() {
+			self.put_char(c);
+		}
+		self.update_cursor();
+	}
+
+	// the hardware cursor is moved once per write, not per character
+	fn put_char(&mut self, character: char) {
+		match self.ansi.advance(character) {
+			Some(Action::Print(character)) => self.print_char(character),
+			Some(action) => self.apply(action),
+			None => {} // inside an escape sequence, or a dropped control code
+		}
+	}
+
+	fn print_char(&mut self, character: char) {
 		match character {
 			'\n' => self.new_line(),
 			'\t' => while self.column_position % 8 != 0 {
-				self.write_byte(b' ');
+				self.put_byte(b' ');
 			},
 			_ => {
-				// unkonwn character
-				let byte = codepage437::encode(character).unwrap_or(6);
-				self.write_byte(byte)
+				// unkonwn characters become the replacement character
+				let byte = codepage437::encode_or_replace(character);
+				self.put_byte(byte)
 			},
 		}
 	}
 
-	// print byte
-	pub fn write_byte(&mut self, byte: u8) {
+	fn put_byte(&mut self, byte: u8) {
 		// auto linefeed
 		if self.column_position >= BUFFER_WIDTH {
 			self.new_line();
 		}
 
-		let row = BUFFER_HEIGHT - 1;
-		let col = self.column_position;
-
 		let color_code = self.color_code;
-		self.buffer.chars[row][col].write(ScreenChar {
+		self.write_cell(self.row_position, self.column_position, ScreenChar {
 			ascii_character: byte,
 			color_code,
 		});
 		self.column_position += 1;
+	}
 
+	fn apply(&mut self, action: Action) {
+		let (row, col) = (self.row_position, self.column_position);
+		let n = |count: u16| usize::from(count);
+		match action {
+			Action::Print(character) => self.print_char(character),
+			Action::Sgr(params) => self.select_graphic_rendition(params),
+			Action::CursorUp(count) => self.move_to(row.saturating_sub(n(count)), col),
+			Action::CursorDown(count) => self.move_to(row + n(count), col),
+			Action::CursorForward(count) => self.move_to(row, col + n(count)),
+			Action::CursorBack(count) => self.move_to(row, col.saturating_sub(n(count))),
+			Action::CursorPosition(r, c) => self.move_to(n(r) - 1, n(c) - 1),
+			Action::CursorColumn(c) => self.move_to(row, n(c) - 1),
+			Action::EraseScreen(mode) => {
+				let cursor = row * BUFFER_WIDTH + col.min(BUFFER_WIDTH - 1);
+				match mode {
+					0 => self.erase(cursor, BUFFER_HEIGHT * BUFFER_WIDTH),
+					1 => self.erase(0, cursor + 1),
+					_ => self.erase(0, BUFFER_HEIGHT * BUFFER_WIDTH),
+				}
+			}
+			Action::EraseLine(mode) => {
+				let start = row * BUFFER_WIDTH;
+				let cursor = start + col.min(BUFFER_WIDTH - 1);
+				match mode {
+					0 => self.erase(cursor, start + BUFFER_WIDTH),
+					1 => self.erase(start, cursor + 1),
+					_ => self.erase(start, start + BUFFER_WIDTH),
+				}
+			}
+			Action::SaveCursor => self.saved_position = (row, col),
+			Action::RestoreCursor => {
+				let (row, col) = self.saved_position;
+				self.move_to(row, col);
+			}
+			Action::ShowCursor(visible) => self.set_cursor_visible(visible),
+		}
 	}
 
-	// print string
-	pub fn write_string(&mut self, s: &str) {
-		for c in s.chars() {
-			self.write_char(c);
+	// like set_position, the hardware cursor follows at the end of the write
+	fn move_to(&mut self, row: usize, column: usize) {
+		self.row_position = row.min(BUFFER_HEIGHT - 1);
+		self.column_position = column.min(BUFFER_WIDTH - 1);
+	}
+
+	// blank the cells from 'start' up to 'end', counted row by row
+	fn erase(&mut self, start: usize, end: usize) {
+		let blank = ScreenChar {
+			ascii_character: b' ',
+			color_code: self.color_code,
+		};
+		for offset in start..end {
+			self.write_cell(offset / BUFFER_WIDTH, offset % BUFFER_WIDTH, blank);
+		}
+	}
+
+	fn select_graphic_rendition(&mut self, params: ansi::Params) {
+		if params.is_empty() {
+			self.reset_graphic_rendition();
+		}
+		let bright = |bold: bool| if bold { 8 } else { 0 };
+		for param in params.iter() {
+			let code = self.color_code;
+			self.color_code = match param {
+				0 => {
+					self.reset_graphic_rendition();
+					continue;
+				}
+				1 => {
+					self.bold = true;
+					code.with_foreground(code.foreground() | 8)
+				}
+				5 => code.with_blink(true),
+				22 => {
+					self.bold = false;
+					code.with_foreground(code.foreground() & 7)
+				}
+				25 => code.with_blink(false),
+				30..=37 => code.with_foreground(ansi::vga_color(param - 30) | bright(self.bold)),
+				39 => code.with_foreground(self.default_color.foreground() | bright(self.bold)),
+				40..=47 => code.with_background(ansi::vga_color(param - 40)),
+				49 => code.with_background(self.default_color.background()),
+				90..=97 => code.with_foreground(ansi::vga_color(param - 90) | 8),
+				100..=107 => code.with_background(ansi::vga_color(param - 100) | 8),
+				_ => code, // underline, italics and the like: the VGA can't
+			};
+		}
+	}
+
+	fn reset_graphic_rendition(&mut self) {
+		self.color_code = self.default_color;
+		self.bold = false;
+	}
+
+	// all screen accesses go through these two
+	// writing returns to the live screen, so any output ends scrolling back
+	fn write_cell(&mut self, row: usize, col: usize, character: ScreenChar) {
+		self.snap_to_live();
+		match (self.screen.as_mut(), self.shadow.as_mut()) {
+			(Some(screen), _) => screen.chars[row][col].write(character),
+			(None, Some(shadow)) => shadow[row][col] = character,
+			(None, None) => unreachable!("console without screen or shadow"),
+		}
+	}
+
+	fn read_cell(&self, row: usize, col: usize) -> ScreenChar {
+		let screen = match (self.screen.as_ref(), self.shadow.as_ref()) {
+			(Some(screen), _) => screen,
+			(None, Some(shadow)) => return shadow[row][col],
+			(None, None) => unreachable!("console without screen or shadow"),
+		};
+		match &self.scrollback {
+			Some(scrollback) if scrollback.is_scrolled() => scrollback.live()[row][col],
+			_ => screen.chars[row][col].read(),
+		}
+	}
+
+	// what the screen shows at 'row' and 'col', scrolled back or not
+	#[cfg(test)]
+	fn screen_cell(&self, row: usize, col: usize) -> ScreenChar {
+		let screen = self.screen.as_ref().expect("console not on the screen");
+		screen.chars[row][col].read()
+	}
+
+	// keep up to 'lines' rows scrolled off the top, allocates
+	pub fn set_scrollback_len(&mut self, lines: usize) {
+		self.snap_to_live();
+		let blank = ScreenChar {
+			ascii_character: b' ',
+			color_code: self.color_code,
+		};
+		self.scrollback = Some(Scrollback::new(lines, blank));
+	}
+
+	// show 'lines' older rows, as far as the scrollback goes
+	pub fn scroll_up(&mut self, lines: usize) {
+		let (screen, scrollback) = match (self.screen.as_ref(), self.scrollback.as_mut()) {
+			(Some(screen), Some(scrollback)) => (screen, scrollback),
+			_ => return,
+		};
+		if !scrollback.is_scrolled() {
+			// keep the live screen to return to
+			for (row, live) in scrollback.live_mut().iter_mut().enumerate() {
+				for (col, cell) in live.iter_mut().enumerate() {
+					*cell = screen.chars[row][col].read();
+				}
+			}
+		}
+		let offset = scrollback.offset() + lines;
+		scrollback.set_offset(offset);
+		self.show_view();
+	}
+
+	// show 'lines' newer rows, back at the live screen at the bottom
+	pub fn scroll_down(&mut self, lines: usize) {
+		if self.screen.is_none() {
+			return;
+		}
+		if let Some(scrollback) = self.scrollback.as_mut() {
+			let offset = scrollback.offset().saturating_sub(lines);
+			scrollback.set_offset(offset);
+			self.show_view();
+		}
+	}
+
+	// stop scrolling back and show the live screen again
+	pub fn snap_to_live(&mut self) {
+		if let Some(scrollback) = self.scrollback.as_mut() {
+			if scrollback.is_scrolled() {
+				scrollback.set_offset(0);
+				self.show_view();
+			}
+		}
+	}
+
+	pub fn is_scrolled_back(&self) -> bool {
+		self.scrollback.as_ref().map_or(false, Scrollback::is_scrolled)
+	}
+
+	// copy what the scrollback offset selects to the screen
+	fn show_view(&mut self) {
+		let (screen, scrollback) = match (self.screen.as_mut(), self.scrollback.as_ref()) {
+			(Some(screen), Some(scrollback)) => (screen, scrollback),
+			_ => return,
+		};
+		for row in 0..BUFFER_HEIGHT {
+			for (col, &cell) in scrollback.view_row(row).iter().enumerate() {
+				screen.chars[row][col].write(cell);
+			}
+		}
+		if scrollback.is_scrolled() {
+			// off the screen, which hides the cursor
+			cursor::set_offset((BUFFER_HEIGHT * BUFFER_WIDTH) as u16);
+		} else {
+			self.update_cursor();
+		}
+	}
+
+	// take the console off the screen and hand the screen over, its cells are
+	// kept in memory from now on; allocates the first time
+	fn deactivate(&mut self) -> Option<&'static mut Buffer> {
+		self.snap_to_live();
+		let screen = self.screen.take()?;
+		let blank = ScreenChar {
+			ascii_character: b' ',
+			color_code: self.color_code,
+		};
+		let shadow = self.shadow.get_or_insert_with(|| Box::new([[blank; BUFFER_WIDTH]; BUFFER_HEIGHT]));
+		for (row, cells) in shadow.iter_mut().enumerate() {
+			for (col, cell) in cells.iter_mut().enumerate() {
+				*cell = screen.chars[row][col].read();
+			}
+		}
+		Some(screen)
+	}
+
+	// put the console's cells and cursor on 'screen', which is the console's
+	// until it is deactivated
+	fn activate(&mut self, screen: &'static mut Buffer) {
+		debug_assert!(self.screen.is_none(), "console already on the screen");
+		if let Some(shadow) = self.shadow.as_ref() {
+			for (row, cells) in shadow.iter().enumerate() {
+				for (col, &cell) in cells.iter().enumerate() {
+					screen.chars[row][col].write(cell);
+				}
+			}
+		}
+		self.screen = Some(screen);
+		// the cursor registers are shared, they take this console's settings
+		cursor::set_shape(self.cursor_shape.unwrap_or_else(cursor::boot_shape));
+		cursor::set_visible(self.cursor_visible);
+		self.update_cursor();
+	}
+
+	// the text of 'row' without trailing blanks
+	pub fn row_text(&self, row: usize) -> String {
+		let mut text: String = (0..BUFFER_WIDTH)
+			.map(|col| codepage437::decode(self.read_cell(row, col).ascii_character))
+			.collect();
+		text.truncate(text.trim_end_matches(|c| c == ' ' || c == '\0').len());
+		text
+	}
+
+	// the whole screen of this console, one line per row
+	pub fn screen_text(&self) -> String {
+		let mut text = String::new();
+		for row in 0..BUFFER_HEIGHT {
+			text.push_str(&self.row_text(row));
+			text.push('\n');
+		}
+		text
+	}
+
+	// (row, column) the next character goes to
+	pub fn position(&self) -> (usize, usize) {
+		(self.row_position, self.column_position)
+	}
+
+	// continue writing at 'row' and 'column', clamped to the screen
+	pub fn set_position(&mut self, row: usize, column: usize) {
+		self.row_position = row.min(BUFFER_HEIGHT - 1);
+		self.column_position = column.min(BUFFER_WIDTH - 1);
+		self.update_cursor();
+	}
+
+	// write 's' starting at 'row' and 'column' without moving the writer,
+	// e.g. for a status bar; cut off at the end of the row, never scrolls
+	pub fn write_at(&mut self, row: usize, column: usize, s: &str) {
+		if row >= BUFFER_HEIGHT {
+			return;
+		}
+		let color_code = self.color_code;
+		for (col, c) in (column..BUFFER_WIDTH).zip(s.chars()) {
+			let byte = codepage437::encode_or_replace(c);
+			self.write_cell(row, col, ScreenChar {
+				ascii_character: byte,
+				color_code,
+			});
+		}
+	}
+
+	pub fn show_cursor(&mut self) {
+		self.set_cursor_visible(true);
+	}
+
+	pub fn hide_cursor(&mut self) {
+		self.set_cursor_visible(false);
+	}
+
+	// the cursor covers scanlines 'start' to 'end' of a cell, 0 is the top
+	// a console off the screen keeps its shape until it is activated
+	pub fn set_cursor_shape(&mut self, start: u8, end: u8) {
+		let end = end.min(cursor::MAX_SCANLINE);
+		let shape = (start.min(end), end);
+		self.cursor_shape = Some(shape);
+		if self.screen.is_some() {
+			cursor::set_shape(shape);
 		}
 	}
 
-	// create a new row
+	fn set_cursor_visible(&mut self, visible: bool) {
+		self.cursor_visible = visible;
+		if self.screen.is_some() {
+			cursor::set_visible(visible);
+		}
+	}
+
+	// the hardware cursor follows the writer, behind the last column
+	// it stays on the last cell until the next character wraps
+	fn update_cursor(&self) {
+		if self.screen.is_none() {
+			return;
+		}
+		let col = self.column_position.min(BUFFER_WIDTH - 1);
+		cursor::set_offset((self.row_position * BUFFER_WIDTH + col) as u16);
+	}
+
+	// move to 'column' of the current row, e.g. to redraw an input line
+	pub(crate) fn set_column(&mut self, column: usize) {
+		self.column_position = column.min(BUFFER_WIDTH - 1);
+		self.update_cursor();
+	}
+
+	// blank the current row from the current column on
+	pub(crate) fn clear_to_end(&mut self) {
+		let blank = ScreenChar {
+			ascii_character: b' ',
+			color_code: self.color_code,
+		};
+		for col in self.column_position..BUFFER_WIDTH {
+			self.write_cell(self.row_position, col, blank);
+		}
+	}
+
+	// blank the whole screen and start over at the top
+	pub(crate) fn clear(&mut self) {
+		for row in 0..BUFFER_HEIGHT {
+			self.clear_row(row);
+		}
+		self.row_position = 0;
+		self.column_position = 0;
+		self.update_cursor();
+	}
+
+	// the colors of everything written from now on, and what escape
+	// sequences reset to
+	pub fn set_colors(&mut self, foreground: Color, background: Color) {
+		self.set_color_code(ColorCode::new(foreground, background));
+	}
+
+	fn set_color_code(&mut self, color_code: ColorCode) {
+		self.color_code = color_code;
+		self.default_color = color_code;
+	}
+
+	// the current and the default colors, to put back later
+	fn color_state(&self) -> (ColorCode, ColorCode) {
+		(self.color_code, self.default_color)
+	}
+
+	fn restore_color_state(&mut self, (color_code, default_color): (ColorCode, ColorCode)) {
+		self.color_code = color_code;
+		self.default_color = default_color;
+	}
+
+	pub fn colors(&self) -> (Color, Color) {
+		(self.foreground(), self.background())
+	}
+
+	pub fn set_foreground(&mut self, foreground: Color) {
+		self.set_color_code(self.color_code.with_foreground(foreground as u8));
+	}
+
+	pub fn set_background(&mut self, background: Color) {
+		self.set_color_code(self.color_code.with_background(background as u8));
+	}
+
+	pub fn foreground(&self) -> Color {
+		Color::from_index(self.color_code.foreground())
+	}
+
+	// a light background means blinking while set_blink_mode is on
+	pub fn background(&self) -> Color {
+		Color::from_index(self.color_code.background())
+	}
+
+	// switch between the normal and the light variant of the foreground
+	pub fn set_bright(&mut self, bright: bool) {
+		let foreground = self.color_code.foreground();
+		let foreground = if bright { foreground | 8 } else { foreground & 7 };
+		self.set_color_code(self.color_code.with_foreground(foreground));
+	}
+
+	// blink what is written from now on, shows as a light background
+	// instead while set_blink_mode is off
+	pub fn set_blink(&mut self, blink: bool) {
+		self.set_color_code(self.color_code.with_blink(blink));
+	}
+
+	pub fn is_blinking(&self) -> bool {
+		self.color_code.background() & 8 != 0
+	}
+
+	// go to the next row, scrolling once the last one is full
 	fn new_line(&mut self) {
+		if self.row_position < BUFFER_HEIGHT - 1 {
+			self.row_position += 1;
+			self.column_position = 0;
+			return;
+		}
+		if self.scrollback.is_some() {
+			let mut top = [self.read_cell(0, 0); BUFFER_WIDTH];
+			for (col, cell) in top.iter_mut().enumerate() {
+				*cell = self.read_cell(0, col);
+			}
+			if let Some(scrollback) = self.scrollback.as_mut() {
+				scrollback.push(top);
+			}
+		}
 		for row in 1..BUFFER_HEIGHT {
 			for col in 0..BUFFER_WIDTH {
-				let character = self.buffer.chars[row][col].read();
-				self.buffer.chars[row - 1][col].write(character);
+				let character = self.read_cell(row, col);
+				self.write_cell(row - 1, col, character);
 			}
 		}
 		self.clear_row(BUFFER_HEIGHT - 1);
@@ -126,7 +628,7 @@ impl Writer {
 			color_code: self.color_code,
 		};
 		for col in 0..BUFFER_WIDTH {
-			self.buffer.chars[row][col].write(blank)
+			self.write_cell(row, col, blank)
 		}
 	}
 }
@@ -140,13 +642,73 @@ impl fmt::Write for Writer {
 
 // static global print interface: Write
 lazy_static! {
+	// starts on the last row, below what the bootloader printed
 	pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
+		row_position: BUFFER_HEIGHT - 1,
 		column_position: 0,
-		color_code: ColorCode::new(Color::Green, Color::Black),
-		buffer: unsafe { &mut *(0xb8000 as *mut Buffer)}, // create a buffer reference pointing to 0xb8000
+		color_code: DEFAULT_COLOR,
+		default_color: DEFAULT_COLOR,
+		// the only reference to 0xb8000, console switches hand it on
+		screen: Some(unsafe { &mut *(0xb8000 as *mut Buffer)}),
+		ansi: ansi::Parser::new(),
+		bold: false,
+		saved_position: (0, 0),
+		scrollback: None,
+		shadow: None,
+		cursor_visible: true,
+		cursor_shape: None,
 	});
 }
 
+// whether bit 7 of the colors blinks, the default, or selects the light
+// background colors; applies to all consoles
+pub fn set_blink_mode(enabled: bool) {
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(|| attribute::set_blink_enabled(enabled));
+}
+
+pub fn blink_mode() -> bool {
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(attribute::is_blink_enabled)
+}
+
+// sets the colors of a writer and restores the previous ones when dropped
+pub struct ColorGuard {
+	writer: &'static Mutex<Writer>,
+	previous: (ColorCode, ColorCode),
+}
+
+impl ColorGuard {
+	// for the first console, which print! writes to
+	pub fn new(foreground: Color, background: Color) -> ColorGuard {
+		ColorGuard::on(&WRITER, foreground, background)
+	}
+
+	pub fn on(writer: &'static Mutex<Writer>, foreground: Color, background: Color) -> ColorGuard {
+		use x86_64::instructions::interrupts;
+		let previous = interrupts::without_interrupts(|| {
+			let mut writer = writer.lock();
+			let previous = writer.color_state();
+			writer.set_colors(foreground, background);
+			previous
+		});
+		ColorGuard { writer, previous }
+	}
+}
+
+impl Drop for ColorGuard {
+	fn drop(&mut self) {
+		use x86_64::instructions::interrupts;
+		interrupts::without_interrupts(|| self.writer.lock().restore_color_state(self.previous));
+	}
+}
+
+// start keeping the rows scrolled off the screen, needs the heap
+pub fn init_scrollback(lines: usize) {
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(|| WRITER.lock().set_scrollback_len(lines));
+}
+
 #[macro_export] // make macro available to the whole crate
 macro_rules! print {
 	($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
@@ -159,6 +721,35 @@ macro_rules! println {
 	($fmt:expr, $($arg:tt)*) => (print!(concat!($fmt, "\n"), $($arg)*));
 }
 
+// print! in another foreground color, the background stays
+#[macro_export]
+macro_rules! cprint {
+	($color:expr, $($arg:tt)*) => ($crate::vga_buffer::_cprint($color, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! cprintln {
+	($color:expr) => ($crate::cprint!($color, "\n"));
+	($color:expr, $fmt:expr) => ($crate::cprint!($color, concat!($fmt, "\n")));
+	($color:expr, $fmt:expr, $($arg:tt)*) => ($crate::cprint!($color, concat!($fmt, "\n"), $($arg)*));
+}
+
+#[doc(hidden)]
+pub fn _cprint(color: Color, args: fmt::Arguments) {
+	use core::fmt::Write;
+	use x86_64::instructions::interrupts;
+
+	// under one lock, so other output can't come out in the color
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		let previous = writer.color_state();
+		writer.set_foreground(color);
+		let result = writer.write_fmt(args);
+		writer.restore_color_state(previous);
+		result.unwrap()
+	});
+}
+
 #[doc(hidden)] // hide the details from the generated documentation
 pub fn _print(args: fmt::Arguments) {
 	use core::fmt::Write;
@@ -191,11 +782,140 @@ fn test_println_output() {
 	interrupts::without_interrupts(|| {
 		let mut writer = WRITER.lock();
 		writeln!(writer, "\n{}", s).expect("writeln failed");
+		// the trailing newline moved the writer to the row below the string
+		let (row, column) = writer.position();
+		assert_eq!(column, 0);
 		for (i, c) in s.chars().enumerate() {
-		// BUFFER_HEIGHT - 2: last screen line will append a newline 
-		let screen_char = writer.buffer.chars[BUFFER_HEIGHT - 2][i].read();
-		assert_eq!(char::from(screen_char.ascii_character), c);
+			let screen_char = writer.read_cell(row - 1, i);
+			assert_eq!(char::from(screen_char.ascii_character), c);
+		}
+	});
+}
+
+#[test_case]
+fn test_write_at_keeps_position() {
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		writer.set_position(3, 10);
+		writer.write_at(0, BUFFER_WIDTH - 2, "status");
+		// cut off at the end of the row
+		assert_eq!(writer.read_cell(0, BUFFER_WIDTH - 1).ascii_character, b't');
+		assert_ne!(writer.read_cell(1, 0).ascii_character, b'u');
+		assert_eq!(writer.position(), (3, 10));
+		writer.write_string("ab");
+		assert_eq!(writer.read_cell(3, 11).ascii_character, b'b');
+		assert_eq!(writer.position(), (3, 12));
+		writer.set_position(BUFFER_HEIGHT + 5, BUFFER_WIDTH + 5);
+		assert_eq!(writer.position(), (BUFFER_HEIGHT - 1, BUFFER_WIDTH - 1));
+	});
+}
+
+#[test_case]
+fn test_ansi_colors_and_cursor() {
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		writer.write_string("\x1b[2;5H\x1b[31mr\x1b[1;44mb\x1b[0mx");
+		let red = writer.read_cell(1, 4);
+		assert_eq!((red.ascii_character, red.color_code), (b'r', ColorCode::new(Color::Red, Color::Black)));
+		let bold = writer.read_cell(1, 5).color_code;
+		assert_eq!(bold, ColorCode::new(Color::LightRed, Color::Blue));
+		assert_eq!(writer.read_cell(1, 6).color_code, DEFAULT_COLOR);
+
+		// save, move, erase the line up to the cursor, restore
+		writer.write_string("\x1b[s\x1b[3D\x1b[1K\x1b[u");
+		assert_eq!(writer.read_cell(1, 4).ascii_character, b' ');
+		assert_eq!(writer.read_cell(1, 6).ascii_character, b'x');
+		assert_eq!(writer.position(), (1, 7));
+		writer.set_position(BUFFER_HEIGHT - 1, 0);
+	});
+}
+
+#[test_case]
+fn test_scrollback_keeps_scrolled_off_rows() {
+	use core::fmt::Write;
+	use x86_64::instructions::interrupts;
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		writer.set_scrollback_len(4);
+		writer.clear();
+		for i in 0..BUFFER_HEIGHT + 5 {
+			write!(writer, "line {:02}\n", i).unwrap();
 		}
+		// lines 00 to 05 scrolled off, the scrollback keeps the last 4
+		writer.scroll_up(10);
+		assert!(writer.is_scrolled_back());
+		assert_eq!(writer.screen_cell(0, 6).ascii_character, b'2');
+		assert_eq!(writer.screen_cell(3, 6).ascii_character, b'5');
+		assert_eq!(writer.screen_cell(4, 6).ascii_character, b'6');
+		// the writer still sees the live screen
+		assert_eq!(writer.read_cell(BUFFER_HEIGHT - 2, 6).ascii_character, b'9');
+
+		writer.scroll_down(1);
+		assert_eq!(writer.screen_cell(0, 6).ascii_character, b'3');
+		// new output returns to the live screen
+		writer.write_string("!");
+		assert!(!writer.is_scrolled_back());
+		assert_eq!(writer.screen_cell(BUFFER_HEIGHT - 1, 0).ascii_character, b'!');
+		writer.scrollback = None;
 	});
+}
+
+#[test_case]
+fn test_color_api() {
+	use x86_64::instructions::interrupts;
+	let before = interrupts::without_interrupts(|| WRITER.lock().color_code);
+	{
+		let _guard = ColorGuard::new(Color::Yellow, Color::Blue);
+		interrupts::without_interrupts(|| {
+			let mut writer = WRITER.lock();
+			assert_eq!(writer.colors(), (Color::Yellow, Color::Blue));
+			writer.set_bright(false);
+			assert_eq!(writer.foreground(), Color::Brown);
+			writer.set_blink(true);
+			assert!(writer.is_blinking());
+			assert_eq!(writer.background(), Color::LightBlue);
+		});
+	}
+	// the guard restored the colors
+	interrupts::without_interrupts(|| assert_eq!(WRITER.lock().color_code, before));
 
-}
\ No newline at end of file
+	// escape sequences reset to the colors set last
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		let state = writer.color_state();
+		writer.set_colors(Color::White, Color::Blue);
+		writer.write_string("\x1b[31;42m\x1b[39mf\x1b[49mb\x1b[0mx");
+		let (row, col) = writer.position();
+		let white_on_blue = ColorCode::new(Color::White, Color::Blue);
+		assert_eq!(writer.read_cell(row, col - 3).color_code, white_on_blue.with_background(Color::Green as u8));
+		assert_eq!(writer.read_cell(row, col - 2).color_code, white_on_blue);
+		assert_eq!(writer.read_cell(row, col - 1).color_code, white_on_blue);
+		writer.restore_color_state(state);
+	});
+
+	cprint!(Color::Red, "r");
+	interrupts::without_interrupts(|| {
+		let writer = WRITER.lock();
+		let (row, col) = writer.position();
+		let cell = writer.read_cell(row, col - 1);
+		assert_eq!((cell.ascii_character, writer.color_code), (b'r', before));
+		assert_eq!(cell.color_code, before.with_foreground(Color::Red as u8));
+	});
+}
+
+#[test_case]
+fn test_read_back_unicode() {
+	use core::fmt::Write;
+	use x86_64::instructions::interrupts;
+	let s = "Hell♂ W♀rld ±½ ░▒▓ ∑";
+	interrupts::without_interrupts(|| {
+		let mut writer = WRITER.lock();
+		writeln!(writer, "\n{}", s).expect("writeln failed");
+		let (row, _) = writer.position();
+		// ∑ is shown as Σ, which is what reads back
+		assert_eq!(writer.row_text(row - 1), "Hell♂ W♀rld ±½ ░▒▓ Σ");
+		assert!(writer.screen_text().contains("W♀rld"));
+	});
+}
@@ -0,0 +1,66 @@
+/* the rows scrolled off the top of the screen, kept on the heap for scrolling back */
+
+use super::{Row, ScreenChar, BUFFER_HEIGHT, BUFFER_WIDTH};
+use alloc::{boxed::Box, collections::VecDeque};
+
+pub const DEFAULT_SCROLLBACK_LEN: usize = 200;
+
+pub(super) struct Scrollback {
+	lines: VecDeque<Row>, // oldest first
+	capacity: usize,
+	offset: usize, // rows scrolled back, 0 shows the live screen
+	live: Box<[Row; BUFFER_HEIGHT]>, // the live screen while scrolled back
+}
+
+impl Scrollback {
+	// all memory is allocated here, so pushing works in interrupt handlers
+	pub(super) fn new(capacity: usize, blank: ScreenChar) -> Scrollback {
+		Scrollback {
+			lines: VecDeque::with_capacity(capacity),
+			capacity,
+			offset: 0,
+			live: Box::new([[blank; BUFFER_WIDTH]; BUFFER_HEIGHT]),
+		}
+	}
+
+	// keep a row scrolled off the screen, dropping the oldest when full
+	pub(super) fn push(&mut self, row: Row) {
+		if self.capacity == 0 {
+			return;
+		}
+		if self.lines.len() == self.capacity {
+			self.lines.pop_front();
+		}
+		self.lines.push_back(row);
+	}
+
+	pub(super) fn offset(&self) -> usize {
+		self.offset
+	}
+
+	pub(super) fn is_scrolled(&self) -> bool {
+		self.offset > 0
+	}
+
+	pub(super) fn live(&self) -> &[Row; BUFFER_HEIGHT] {
+		&self.live
+	}
+
+	pub(super) fn live_mut(&mut self) -> &mut [Row; BUFFER_HEIGHT] {
+		&mut self.live
+	}
+
+	// the new offset is clamped to the rows there are
+	pub(super) fn set_offset(&mut self, offset: usize) {
+		self.offset = offset.min(self.lines.len());
+	}
+
+	// what screen row 'row' shows at the current offset
+	pub(super) fn view_row(&self, row: usize) -> &Row {
+		let index = self.lines.len() - self.offset + row;
+		match self.lines.get(index) {
+			Some(line) => line,
+			None => &self.live[index - self.lines.len()],
+		}
+	}
+}
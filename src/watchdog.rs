@@ -0,0 +1,144 @@
+/* watchdog: notice when the kernel stops making progress and dump the CPU state to serial
+
+   What is covered:
+   - a CPU that hangs with interrupts enabled, by its own timer interrupt
+   - a CPU that hangs with interrupts disabled, e.g. spinning on WRITER or
+     SERIAL1, by another CPU, which stops it with an NMI
+
+   What is not:
+   - a hang with interrupts disabled on a single CPU boot (-smp 1), no CPU
+     is left to notice it
+   - the BSP hanging with interrupts disabled while the PIT is the clock
+     source, the clock stands still with it
+*/
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+use crate::emergency_println;
+use crate::smp::{self, MAX_CPUS};
+use crate::time;
+
+// how many stack slots to print below the interrupted stack pointer
+const STACK_DUMP_WORDS: usize = 16;
+// how often the other CPUs are checked
+pub const CHECK_PERIOD_MS: u64 = 100;
+
+const NOT_FIRED: AtomicBool = AtomicBool::new(false);
+const NEVER: AtomicU64 = AtomicU64::new(0);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TIMEOUT_NS: AtomicU64 = AtomicU64::new(0);
+// time of the last call to 'pet' on each CPU, in monotonic nanoseconds
+static LAST_PET: [AtomicU64; MAX_CPUS] = [NEVER; MAX_CPUS];
+// only dump once per hang
+static FIRED: [AtomicBool; MAX_CPUS] = [NOT_FIRED; MAX_CPUS];
+// set before another CPU sends the NMI, so its handler knows why
+static NMI_REQUESTED: [AtomicBool; MAX_CPUS] = [NOT_FIRED; MAX_CPUS];
+// stuck CPUs that took the NMI another CPU sent them
+static STOPPED: AtomicU64 = AtomicU64::new(0);
+
+// start watching, every CPU must call 'pet' at least every 'timeout_ms'
+pub fn enable(timeout_ms: u64) {
+    TIMEOUT_NS.store(timeout_ms * 1_000_000, Ordering::Relaxed);
+    let now = time::monotonic_nanos();
+    for (last, fired) in LAST_PET.iter().zip(FIRED.iter()) {
+        last.store(now, Ordering::Relaxed);
+        fired.store(false, Ordering::Relaxed);
+    }
+    ENABLED.store(true, Ordering::Release);
+}
+
+pub fn disable() {
+    ENABLED.store(false, Ordering::Release);
+}
+
+// signal forward progress of the current CPU, called from the executor and
+// idle loops
+pub fn pet() {
+    let cpu = smp::current_cpu();
+    LAST_PET[cpu].store(time::monotonic_nanos(), Ordering::Relaxed);
+    FIRED[cpu].store(false, Ordering::Relaxed);
+}
+
+// how long 'cpu' went without petting, if that is past the timeout and
+// was not reported yet
+fn stalled(cpu: usize) -> Option<u64> {
+    let stalled = time::monotonic_nanos().saturating_sub(LAST_PET[cpu].load(Ordering::Relaxed));
+    if stalled > TIMEOUT_NS.load(Ordering::Relaxed) && !FIRED[cpu].swap(true, Ordering::Relaxed) {
+        Some(stalled / 1_000_000)
+    } else {
+        None
+    }
+}
+
+// Called by the timer interrupt handler on the BSP
+// A hang with interrupts enabled is dumped right here, the other CPUs are
+// checked as in check_other_cpus
+pub(crate) fn check(stack_frame: &InterruptStackFrame) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    if let Some(ms) = stalled(smp::current_cpu()) {
+        emergency_println!("\nWATCHDOG: no progress for {} ms", ms);
+        dump(stack_frame);
+    }
+    check_other_cpus();
+}
+
+// Called by the timer interrupt handlers of all CPUs
+// A CPU spinning with interrupts disabled never sees its own timer, so the
+// CPUs watch each other and stop a stuck one with an NMI, which it can't mask
+pub(crate) fn check_other_cpus() {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    let current = smp::current_cpu();
+    for cpu in (0..smp::cpu_count()).filter(|&cpu| cpu != current) {
+        let apic_id = match smp::apic_id(cpu) {
+            Some(apic_id) => apic_id,
+            None => continue,
+        };
+        if let Some(ms) = stalled(cpu) {
+            emergency_println!("\nWATCHDOG: CPU {} made no progress for {} ms", cpu, ms);
+            NMI_REQUESTED[cpu].store(true, Ordering::Release);
+            smp::apic::send_nmi(apic_id);
+        }
+    }
+}
+
+// Called by the NMI handler
+pub(crate) fn nmi(stack_frame: &InterruptStackFrame) {
+    let cpu = smp::current_cpu();
+    if NMI_REQUESTED[cpu].swap(false, Ordering::Acquire) {
+        STOPPED.fetch_add(1, Ordering::Relaxed);
+        emergency_println!("\nWATCHDOG: NMI on stuck CPU {}", cpu);
+    } else {
+        emergency_println!("\nNMI received on CPU {}", cpu);
+    }
+    dump(stack_frame);
+}
+
+// how many stuck CPUs were stopped with an NMI since boot
+pub fn stopped_cpus() -> u64 {
+    STOPPED.load(Ordering::Relaxed)
+}
+
+// print the interrupted context without taking any lock
+fn dump(stack_frame: &InterruptStackFrame) {
+    use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+
+    emergency_println!("uptime: {} ticks", time::ticks());
+    emergency_println!("{:#?}", stack_frame);
+    emergency_println!("CR0: {:?}", Cr0::read());
+    emergency_println!("CR2: {:?}", Cr2::read());
+    emergency_println!("CR3: {:?}", Cr3::read());
+    emergency_println!("CR4: {:?}", Cr4::read());
+
+    // the interrupted stack is mapped, otherwise we would have double faulted
+    let stack = stack_frame.stack_pointer.as_ptr::<u64>();
+    emergency_println!("stack:");
+    for i in 0..STACK_DUMP_WORDS {
+        let value = unsafe { stack.add(i).read_volatile() };
+        emergency_println!("  {:#018x}: {:#018x}", stack as u64 + (i * 8) as u64, value);
+    }
+}
@@ -0,0 +1,276 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(blog_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::Cell;
+use core::future::pending;
+use blog_os::task::{Task, Priority, executor::Executor, join::JoinError, stats, yield_now};
+use blog_os::task::sync::{Mutex, Notify, Semaphore};
+use blog_os::task::channel::{mpsc, oneshot, broadcast};
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use futures_util::FutureExt;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use blog_os::allocator;
+    use blog_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    blog_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe {
+        memory::init(phys_mem_offset)
+    };
+    let mut frame_allocator = unsafe {
+        BootInfoFrameAllocator::init(&boot_info.memory_map)
+    };
+    allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("heap initalization failed");
+
+    test_main();
+    loop {}
+}
+
+
+// a JoinHandle resolves to the output of its task
+#[test_case]
+fn join_handle_returns_output() {
+    let mut executor = Executor::new();
+    let handle = executor.spawn_with_handle(async { 6 * 7 });
+    let result = Rc::new(Cell::new(None));
+    let result_clone = result.clone();
+    executor.spawn(Task::new(async move {
+        result_clone.set(Some(handle.await));
+    }));
+
+    executor.run_until_idle();
+    assert_eq!(result.get(), Some(Ok(42)));
+    assert_eq!(executor.task_count(), 0);
+}
+
+// a detached task still runs to completion
+#[test_case]
+fn detached_task_completes() {
+    let mut executor = Executor::new();
+    let ran = Rc::new(Cell::new(false));
+    let ran_clone = ran.clone();
+    executor.spawn_with_handle(async move { ran_clone.set(true) }).detach();
+
+    executor.run_until_idle();
+    assert!(ran.get());
+}
+
+// a task dropped before completing reports cancellation
+#[test_case]
+fn dropped_task_is_cancelled() {
+    let mut executor = Executor::new();
+    let handle = executor.spawn_with_handle(pending::<()>());
+    executor.run_until_idle();
+    assert!(!handle.is_finished());
+
+    drop(executor);
+    assert_eq!(handle.now_or_never(), Some(Err(JoinError::Cancelled)));
+}
+
+// aborting a task blocked on the keyboard drops its future, which releases
+// the scancode stream and everything else the task owned
+#[test_case]
+fn abort_releases_keyboard_stream() {
+    use blog_os::task::keyboard::ScancodeStream;
+    use futures_util::StreamExt;
+
+    let mut executor = Executor::new();
+    let owned = Rc::new(());
+    let owned_clone = owned.clone();
+    let handle = executor.spawn_with_handle(async move {
+        let _owned = owned_clone;
+        let mut scancodes = ScancodeStream::new();
+        scancodes.next().await
+    });
+    executor.run_until_idle();
+    assert_eq!(Rc::strong_count(&owned), 2); // still waiting for a key
+
+    handle.abort_handle().abort();
+    executor.run_until_idle();
+    assert_eq!(executor.task_count(), 0);
+    assert_eq!(Rc::strong_count(&owned), 1);
+    assert_eq!(handle.now_or_never(), Some(Err(JoinError::Cancelled)));
+
+    // the keyboard can be read again
+    drop(ScancodeStream::new());
+}
+
+// a task waiting for an async mutex gets it once the holder is done
+#[test_case]
+fn mutex_waits_for_holder() {
+    let mut executor = Executor::new();
+    let mutex = Rc::new(Mutex::new(Vec::new()));
+    let notify = Rc::new(Notify::new());
+
+    let (mutex_clone, notify_clone) = (mutex.clone(), notify.clone());
+    executor.spawn(Task::new(async move {
+        let mut log = mutex_clone.lock().await;
+        notify_clone.notified().await; // keep holding the lock
+        log.push(1);
+    }));
+    let mutex_clone = mutex.clone();
+    executor.spawn(Task::new(async move {
+        mutex_clone.lock().await.push(2);
+    }));
+    executor.run_until_idle();
+    assert!(mutex.try_lock().is_none());
+
+    notify.notify_one();
+    executor.run_until_idle();
+    assert_eq!(*mutex.try_lock().unwrap(), [1, 2]);
+}
+
+// no more tasks than permits get past acquire()
+#[test_case]
+fn semaphore_limits_concurrency() {
+    let mut executor = Executor::new();
+    let semaphore = Rc::new(Semaphore::new(2));
+    let notify = Rc::new(Notify::new());
+    let running = Rc::new(Cell::new(0));
+
+    for _ in 0..3 {
+        let (semaphore, notify, running) = (semaphore.clone(), notify.clone(), running.clone());
+        executor.spawn(Task::new(async move {
+            let _permit = semaphore.acquire().await;
+            running.set(running.get() + 1);
+            notify.notified().await;
+        }));
+    }
+    executor.run_until_idle();
+    assert_eq!(running.get(), 2);
+    assert_eq!(semaphore.available_permits(), 0);
+
+    // the first two finish and hand their permits to the third
+    notify.notify_waiters();
+    executor.run_until_idle();
+    assert_eq!(running.get(), 3);
+    assert_eq!(semaphore.available_permits(), 1);
+}
+
+// a bounded sender waits for room and the receiver sees the channel close
+#[test_case]
+fn mpsc_bounded_backpressure() {
+    let mut executor = Executor::new();
+    let (sender, mut receiver) = mpsc::channel(2);
+    let sent = Rc::new(Cell::new(0));
+
+    let sent_clone = sent.clone();
+    executor.spawn(Task::new(async move {
+        for i in 0..5 {
+            sender.send(i).await.unwrap();
+            sent_clone.set(sent_clone.get() + 1);
+        }
+    }));
+    executor.run_until_idle();
+    assert_eq!(sent.get(), 2); // blocked on the full channel
+
+    let received = Rc::new(Cell::new(0));
+    let received_clone = received.clone();
+    executor.spawn(Task::new(async move {
+        while let Some(i) = receiver.recv().await {
+            assert_eq!(i, received_clone.get());
+            received_clone.set(i + 1);
+        }
+    }));
+    executor.run_until_idle();
+    assert_eq!(sent.get(), 5);
+    assert_eq!(received.get(), 5);
+    assert_eq!(executor.task_count(), 0); // the receiver saw the sender go away
+}
+
+#[test_case]
+fn oneshot_delivers_value_or_error() {
+    let (sender, receiver) = oneshot::channel();
+    sender.send(7).unwrap();
+    assert_eq!(receiver.now_or_never(), Some(Ok(7)));
+
+    let (sender, receiver) = oneshot::channel::<u8>();
+    drop(sender);
+    assert_eq!(receiver.now_or_never(), Some(Err(oneshot::RecvError)));
+}
+
+// every subscriber gets every value, a slow one learns what it missed
+#[test_case]
+fn broadcast_reaches_all_receivers() {
+    let (sender, mut first) = broadcast::channel(2);
+    let mut second = sender.subscribe();
+    assert_eq!(sender.send(1), 2);
+    assert_eq!(first.try_recv(), Some(Ok(1)));
+    assert_eq!(second.try_recv(), Some(Ok(1)));
+
+    for i in 2..5 {
+        sender.send(i);
+    }
+    assert_eq!(first.try_recv(), Some(Err(broadcast::RecvError::Lagged(1))));
+    assert_eq!(first.try_recv(), Some(Ok(3)));
+    assert_eq!(first.try_recv(), Some(Ok(4)));
+    assert_eq!(first.try_recv(), None);
+
+    drop(sender);
+    assert_eq!(first.try_recv(), Some(Err(broadcast::RecvError::Closed)));
+}
+
+// a task that yields forever gets a bounded share and can't starve others
+#[test_case]
+fn yielding_task_does_not_starve_low_priority() {
+    let mut executor = Executor::new();
+    let low_ran = Rc::new(Cell::new(false));
+    let spins = Rc::new(Cell::new(0));
+
+    let (low_ran_clone, spins_clone) = (low_ran.clone(), spins.clone());
+    let busy = Task::new(async move {
+        while !low_ran_clone.get() {
+            spins_clone.set(spins_clone.get() + 1);
+            yield_now().await;
+        }
+    }).with_priority(Priority::High);
+    let low_ran_clone = low_ran.clone();
+    let low = Task::new(async move {
+        low_ran_clone.set(true);
+    }).with_priority(Priority::Low);
+    executor.spawn(busy);
+    executor.spawn(low);
+
+    executor.run_until_idle();
+    assert!(low_ran.get());
+    assert!(spins.get() <= Priority::High.weight());
+}
+
+#[test_case]
+fn stats_count_polls() {
+    let mut executor = Executor::new();
+    let notify = Rc::new(Notify::new());
+    let notify_clone = notify.clone();
+    let task = Task::new(async move {
+        notify_clone.notified().await;
+    }).with_priority(Priority::Low);
+    let task_id = task.id();
+    executor.spawn(task);
+
+    executor.run_until_idle();
+    let task_stats = stats::get(task_id).unwrap();
+    assert_eq!(task_stats.polls, 1);
+    assert_eq!(task_stats.priority, Priority::Low);
+
+    notify.notify_one();
+    executor.run_until_idle();
+    assert!(stats::get(task_id).is_none()); // gone with the task
+}
+
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blog_os::test_panic_handler(info)
+}
@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(blog_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use core::hint;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use blog_os::{thread, time};
+use blog_os::thread::{ThreadState, WaitQueue};
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use blog_os::allocator;
+    use blog_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    blog_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe {
+        memory::init(phys_mem_offset)
+    };
+    let mut frame_allocator = unsafe {
+        BootInfoFrameAllocator::init(&boot_info.memory_map)
+    };
+    allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("heap initalization failed");
+    thread::init();
+
+    test_main();
+    loop {}
+}
+
+static FIRST: AtomicU64 = AtomicU64::new(0);
+static SECOND: AtomicU64 = AtomicU64::new(0);
+static STOP: AtomicBool = AtomicBool::new(false);
+
+// neither thread ever yields, only the timer tick lets the other one run
+#[test_case]
+fn cpu_bound_threads_both_progress() {
+    let first = thread::spawn(|| {
+        while !STOP.load(Ordering::Relaxed) {
+            FIRST.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let second = thread::spawn(|| {
+        while !STOP.load(Ordering::Relaxed) {
+            SECOND.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+
+    // spin as well, this thread is preempted just like the others
+    let start = time::ticks();
+    while time::ticks() < start + 20 {
+        hint::spin_loop();
+    }
+    assert!(FIRST.load(Ordering::Relaxed) > 0);
+    assert!(SECOND.load(Ordering::Relaxed) > 0);
+    STOP.store(true, Ordering::Relaxed);
+    first.join();
+    second.join();
+}
+
+#[test_case]
+fn join_returns_result() {
+    let handle = thread::spawn(|| (1..=100u64).sum::<u64>());
+    let id = handle.id();
+    assert_eq!(handle.join(), 5050);
+    assert_ne!(id, thread::current());
+}
+
+// exit() ends a thread early, without a result
+#[test_case]
+fn exit_ends_thread() {
+    let handle = thread::spawn(|| {
+        thread::exit();
+    });
+    let id = handle.id();
+    while thread::state(id).is_some() {
+        thread::yield_now();
+    }
+    assert!(!handle.is_finished());
+}
+
+#[test_case]
+fn sleep_ms_waits_for_deadline() {
+    let start = time::ticks();
+    thread::sleep_ms(50);
+    // 50 ms are 5 ticks at 100 Hz
+    assert!(time::ticks() >= start + 5);
+}
+
+static QUEUE: WaitQueue = WaitQueue::new();
+static WOKEN: AtomicU64 = AtomicU64::new(0);
+
+#[test_case]
+fn wait_queue_blocks_until_notified() {
+    let handle = thread::spawn(|| {
+        QUEUE.wait_until(|| WOKEN.load(Ordering::Relaxed) > 0);
+        WOKEN.load(Ordering::Relaxed)
+    });
+    let id = handle.id();
+    while thread::state(id) != Some(ThreadState::Blocked) {
+        thread::yield_now();
+    }
+    WOKEN.store(7, Ordering::Relaxed);
+    assert!(QUEUE.notify_one());
+    assert_eq!(handle.join(), 7);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blog_os::test_panic_handler(info)
+}
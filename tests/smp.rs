@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(blog_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use blog_os::{smp, task::work_stealing, time, watchdog};
+use core::hint;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use futures_util::FutureExt;
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use blog_os::allocator;
+    use blog_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    blog_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe {
+        memory::init(phys_mem_offset)
+    };
+    let mut frame_allocator = unsafe {
+        BootInfoFrameAllocator::init(&boot_info.memory_map)
+    };
+    allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("heap initalization failed");
+    blog_os::time::init();
+    smp::init(&mut mapper, &mut frame_allocator);
+
+    test_main();
+    loop {}
+}
+
+// the tests run QEMU with -smp 4
+#[test_case]
+fn all_cpus_online() {
+    assert_eq!(smp::cpu_count(), 4);
+}
+
+#[test_case]
+fn bsp_is_cpu_zero() {
+    assert_eq!(smp::current_cpu(), 0);
+}
+
+#[test_case]
+fn per_cpu_data_matches_apic() {
+    let data = smp::percpu::current().expect("per-CPU data not set up");
+    assert_eq!(data.cpu(), 0);
+    assert_eq!(data.apic_id(), smp::apic::id());
+}
+
+static RAN_ON: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicUsize = AtomicUsize::new(0);
+
+// the BSP never runs the executor here, so idle APs must steal the tasks
+#[test_case]
+fn idle_cpus_steal_tasks() {
+    for _ in 0..8 {
+        work_stealing::spawn(async {
+            RAN_ON.fetch_or(1 << smp::current_cpu(), Ordering::Relaxed);
+            DONE.fetch_add(1, Ordering::Release);
+        });
+    }
+    while DONE.load(Ordering::Acquire) < 8 {
+        hint::spin_loop();
+    }
+    let ran_on = RAN_ON.load(Ordering::Relaxed);
+    assert_eq!(ran_on & 1, 0);
+    assert_ne!(ran_on, 0);
+}
+
+#[test_case]
+fn handle_returns_output_from_other_cpu() {
+    let handle = work_stealing::spawn_with_handle(async { smp::current_cpu() });
+    while !handle.is_finished() {
+        hint::spin_loop();
+    }
+    let cpu = handle.now_or_never().unwrap().unwrap();
+    assert_ne!(cpu, 0);
+}
+
+static RELEASE: AtomicBool = AtomicBool::new(false);
+
+// a CPU spinning with interrupts disabled is stopped by the others, which is
+// all the watchdog can do for it; -smp 1 has no other CPU
+#[test_case]
+fn watchdog_stops_cpu_spinning_with_interrupts_disabled() {
+    use x86_64::instructions::interrupts;
+
+    let stopped = watchdog::stopped_cpus();
+    watchdog::enable(500);
+    let handle = work_stealing::spawn_with_handle(async {
+        interrupts::without_interrupts(|| {
+            while !RELEASE.load(Ordering::Acquire) {
+                hint::spin_loop();
+            }
+        });
+        smp::current_cpu()
+    });
+    let deadline = time::uptime() + Duration::from_secs(5);
+    while watchdog::stopped_cpus() == stopped {
+        assert!(time::uptime() < deadline, "stuck CPU was not stopped");
+        watchdog::pet();
+        hint::spin_loop();
+    }
+    RELEASE.store(true, Ordering::Release);
+    while !handle.is_finished() {
+        watchdog::pet();
+        hint::spin_loop();
+    }
+    watchdog::disable();
+    assert_ne!(handle.now_or_never().unwrap().unwrap(), 0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blog_os::test_panic_handler(info)
+}